@@ -1,12 +1,12 @@
-use std::io::{BufReader, Read, Write};
-use std::iter::Peekable;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
 
 pub type RepairResult = Result<RepairOk, RepairErr>;
 
 #[derive(Debug)]
 pub enum RepairOk {
     Valid,
-    Repaired,
+    Repaired(RepairReport),
 }
 
 #[derive(Debug)]
@@ -15,6 +15,19 @@ pub enum RepairErr {
     IoErr(std::io::Error),
 }
 
+impl RepairOk {
+    fn from_fixes(fixes: Vec<Fix>) -> Self {
+        if fixes.is_empty() {
+            Self::Valid
+        } else {
+            Self::Repaired(RepairReport {
+                fixes,
+                extracted: Vec::new(),
+            })
+        }
+    }
+}
+
 impl From<std::io::Error> for RepairErr {
     fn from(value: std::io::Error) -> Self {
         Self::IoErr(value)
@@ -27,571 +40,6396 @@ impl From<SyntaxError> for RepairErr {
     }
 }
 
+/// The list of fixes applied to the input, in input order.
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    fixes: Vec<Fix>,
+    extracted: Vec<Span>,
+}
+
+impl RepairReport {
+    pub fn fixes(&self) -> &[Fix] {
+        &self.fixes
+    }
+
+    /// Returns the input spans of the values taken by [`RepairOptions::extract`], in input order.
+    pub fn extracted(&self) -> &[Span] {
+        &self.extracted
+    }
+}
+
+/// The result of a line repaired by [`repair_lines`].
 #[derive(Debug)]
-pub enum SyntaxError {
-    UnexpectedEof,
-    InvalidValue,
-    TrailingData,
+pub struct LineResult {
+    /// The line number, starting from 1.
+    pub line: u64,
+    /// The input span of the line, excluding the line break.
+    pub input: Span,
+    /// The result of the line, or the reason why it could not be repaired.
+    pub result: Result<RepairOk, SyntaxError>,
 }
 
-impl std::fmt::Display for SyntaxError {
+/// A top-level value repaired by [`repair_stream`].
+#[derive(Debug)]
+pub struct StreamValue<'a> {
+    /// The input span of the value.
+    pub input: Span,
+    /// The repaired value. The output spans of the fixes are relative to it.
+    pub output: &'a [u8],
+    pub result: RepairOk,
+}
+
+/// A single fix applied to the input.
+///
+/// `input` is the span of the input that was changed and `output` is the span of the output that
+/// replaced it. Either of them is empty when the fix only removes or inserts something.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub kind: FixKind,
+    pub input: Span,
+    pub output: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixKind {
+    TrailingCommaRemoved,
+    MissingCommaInserted,
+    CommentRemoved,
+    UnterminatedStringClosed,
+    IncompleteValueRemoved,
+    UnclosedContainerClosed,
+    SingleQuotesReplaced,
+    KeyQuoted,
+    LiteralReplaced,
+    LeadingZeroRemoved,
+    PlusSignRemoved,
+    ZeroInserted,
+    RadixConverted,
+    ControlCharacterEscaped,
+    EscapeReplaced,
+    InvalidUtf8Replaced,
+    LoneSurrogateReplaced,
+    BomRemoved,
+    Utf16Transcoded,
+    TextRemoved,
+    WrapperRemoved,
+    SeparatorReplaced,
+    ArrayWrapped,
+    RecordSeparatorInserted,
+    LineFeedInserted,
+    TruncatedElementRemoved,
+    InvalidElementRemoved,
+    EmptyElementRemoved,
+    CloserReplaced,
+    CloserInserted,
+    ColonInserted,
+    ValueInserted,
+    LoneKeyCompleted,
+    LoneKeyRemoved,
+}
+
+impl std::fmt::Display for FixKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::UnexpectedEof => write!(f, "unexpected end of file"),
-            Self::InvalidValue => write!(f, "invalid value"),
-            Self::TrailingData => write!(f, "unexpected data at the end"),
+            Self::TrailingCommaRemoved => write!(f, "removed trailing comma"),
+            Self::MissingCommaInserted => write!(f, "inserted missing comma"),
+            Self::CommentRemoved => write!(f, "removed comment"),
+            Self::UnterminatedStringClosed => write!(f, "closed unterminated string"),
+            Self::IncompleteValueRemoved => write!(f, "removed incomplete value"),
+            Self::UnclosedContainerClosed => write!(f, "closed unclosed container"),
+            Self::SingleQuotesReplaced => write!(f, "replaced single quotes with double quotes"),
+            Self::KeyQuoted => write!(f, "quoted unquoted key"),
+            Self::LiteralReplaced => write!(f, "replaced non-JSON literal"),
+            Self::LeadingZeroRemoved => write!(f, "removed leading zero"),
+            Self::PlusSignRemoved => write!(f, "removed plus sign"),
+            Self::ZeroInserted => write!(f, "inserted missing zero"),
+            Self::RadixConverted => write!(f, "converted number to decimal"),
+            Self::ControlCharacterEscaped => write!(f, "escaped control character"),
+            Self::EscapeReplaced => write!(f, "replaced invalid escape sequence"),
+            Self::InvalidUtf8Replaced => write!(f, "replaced invalid UTF-8 sequence"),
+            Self::LoneSurrogateReplaced => write!(f, "replaced unpaired surrogate"),
+            Self::BomRemoved => write!(f, "removed byte order mark"),
+            Self::Utf16Transcoded => write!(f, "transcoded UTF-16 to UTF-8"),
+            Self::TextRemoved => write!(f, "removed text around JSON"),
+            Self::WrapperRemoved => write!(f, "removed wrapper around JSON"),
+            Self::SeparatorReplaced => write!(f, "replaced whitespace between values"),
+            Self::ArrayWrapped => write!(f, "wrapped values in an array"),
+            Self::RecordSeparatorInserted => {
+                write!(f, "inserted record separator before sequence element")
+            }
+            Self::LineFeedInserted => write!(f, "inserted line feed after sequence element"),
+            Self::TruncatedElementRemoved => write!(f, "removed truncated sequence element"),
+            Self::InvalidElementRemoved => write!(f, "removed invalid sequence element"),
+            Self::EmptyElementRemoved => write!(f, "removed empty sequence element"),
+            Self::CloserReplaced => write!(f, "replaced mismatched closing bracket"),
+            Self::CloserInserted => write!(f, "inserted missing closing bracket"),
+            Self::ColonInserted => write!(f, "inserted missing colon"),
+            Self::ValueInserted => write!(f, "inserted missing value"),
+            Self::LoneKeyCompleted => write!(f, "inserted value for key without value"),
+            Self::LoneKeyRemoved => write!(f, "removed key without value"),
         }
     }
 }
 
-pub fn repair(r: impl Read, mut w: impl Write) -> RepairResult {
-    let mut r = BufReader::new(r).bytes().peekable();
-    let mut p = Parser::new(&mut r, &mut w);
-    match p.walk_json() {
-        Ok(_) => Ok(if p.repaired() {
-            RepairOk::Repaired
+/// A half-open range of byte offsets, `start..end`. Input offsets are counted as in [`Position`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Span {
+    fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+
+    fn empty(at: u64) -> Self {
+        Self::new(at, at)
+    }
+}
+
+/// A location in the input.
+///
+/// `offset` is the 0-based byte offset from the beginning of the input. `line` and `column` are
+/// 1-based, and `column` is counted in bytes. UTF-16 input is transcoded to UTF-8 first, and its
+/// offsets and columns are counted in the transcoded bytes, including the 3-byte UTF-8 byte order
+/// mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub offset: u64,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl Position {
+    fn start() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn advance(&mut self, c: u8) {
+        self.offset += 1;
+        if c == b'\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            RepairOk::Valid
-        }),
-        Err(err) => Err(err),
+            self.column += 1;
+        }
     }
 }
 
-struct Parser<'input, 'output, I: ByteStream, W: Write> {
-    input: &'input mut I,
-    output: &'output mut W,
-    repaired: bool,
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
-type ParserResult = Result<(), RepairErr>;
+/// What the parser was expecting when it encountered a syntax error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Value,
+    Key,
+    Byte(u8),
+    OneOf(&'static [u8]),
+    Digit,
+    HexDigit,
+    Escape,
+    StringChar,
+    CommentEnd,
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Value => write!(f, "a value"),
+            Self::Key => write!(f, "an object key"),
+            Self::Byte(c) => write!(f, "'{}'", char::from(*c)),
+            Self::OneOf(cs) => {
+                for (i, c) in cs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", if i == cs.len() - 1 { " or " } else { ", " })?;
+                    }
+                    write!(f, "'{}'", char::from(*c))?;
+                }
+                Ok(())
+            }
+            Self::Digit => write!(f, "a digit"),
+            Self::HexDigit => write!(f, "a hexadecimal digit"),
+            Self::Escape => write!(f, "an escape sequence"),
+            Self::StringChar => write!(f, "a string character"),
+            Self::CommentEnd => write!(f, "'*/'"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SyntaxError {
+    UnexpectedEof {
+        pos: Position,
+        expected: Expected,
+    },
+    InvalidValue {
+        pos: Position,
+        expected: Expected,
+    },
+    TrailingData {
+        pos: Position,
+    },
+    /// The containers are nested deeper than [`RepairOptions::max_depth`].
+    TooDeep {
+        pos: Position,
+        limit: usize,
+    },
+    /// The input is larger than [`RepairOptions::max_size`].
+    TooLarge {
+        pos: Position,
+        limit: u64,
+    },
+    /// A string is longer than [`RepairOptions::max_string_length`].
+    StringTooLong {
+        pos: Position,
+        limit: u64,
+    },
+    /// A number is longer than [`RepairOptions::max_number_length`].
+    NumberTooLong {
+        pos: Position,
+        limit: u64,
+    },
+    /// `NaN`, `Infinity` or `-Infinity` was found and [`NonFinite::Error`] is set.
+    NonFiniteNumber {
+        pos: Position,
+    },
+    /// A hexadecimal, octal or binary literal is too large to be converted by [`Rule::Radix`].
+    UnrepresentableNumber {
+        pos: Position,
+    },
+    /// A string or an unquoted key contains a byte sequence that is not valid UTF-8.
+    InvalidUtf8 {
+        pos: Position,
+    },
+    /// A `\uXXXX` escape of a surrogate is not paired and [`RepairOptions::check_surrogates`]
+    /// is set.
+    LoneSurrogate {
+        pos: Position,
+    },
+    /// The input starts with a byte order mark, which is neither removed by [`Rule::Bom`] nor
+    /// kept by [`RepairOptions::keep_bom`].
+    ByteOrderMark {
+        pos: Position,
+    },
+    /// The UTF-16 input contains an unpaired surrogate or ends in the middle of a code unit.
+    InvalidUtf16 {
+        pos: Position,
+    },
+    /// The input of [`repair_lines`] starts with a UTF-16 byte order mark. JSON Lines must be
+    /// UTF-8.
+    Utf16Unsupported {
+        pos: Position,
+    },
+}
 
-trait ByteStream {
-    fn next(&mut self) -> Result<std::io::Result<u8>, SyntaxError> {
-        match self.try_next() {
-            Some(ret) => Ok(ret),
-            None => Err(SyntaxError::UnexpectedEof),
+impl SyntaxError {
+    /// Returns the location in the input where the error was detected.
+    pub fn position(&self) -> Position {
+        match self {
+            Self::UnexpectedEof { pos, .. } => *pos,
+            Self::InvalidValue { pos, .. } => *pos,
+            Self::TrailingData { pos } => *pos,
+            Self::TooDeep { pos, .. } => *pos,
+            Self::TooLarge { pos, .. } => *pos,
+            Self::StringTooLong { pos, .. } => *pos,
+            Self::NumberTooLong { pos, .. } => *pos,
+            Self::NonFiniteNumber { pos } => *pos,
+            Self::UnrepresentableNumber { pos } => *pos,
+            Self::InvalidUtf8 { pos } => *pos,
+            Self::LoneSurrogate { pos } => *pos,
+            Self::ByteOrderMark { pos } => *pos,
+            Self::InvalidUtf16 { pos } => *pos,
+            Self::Utf16Unsupported { pos } => *pos,
         }
     }
+}
 
-    fn peek(&mut self) -> Result<std::io::Result<u8>, SyntaxError> {
-        match self.try_peek() {
-            Some(ret) => Ok(ret),
-            None => Err(SyntaxError::UnexpectedEof),
+/// Formats the error message without its position. Use [`SyntaxError::position`] to get the
+/// location.
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof { expected, .. } => {
+                write!(f, "unexpected end of file, expected {}", expected)
+            }
+            Self::InvalidValue { expected, .. } => {
+                write!(f, "invalid value, expected {}", expected)
+            }
+            Self::TrailingData { .. } => write!(f, "unexpected data at the end"),
+            Self::TooDeep { limit, .. } => {
+                write!(f, "nesting depth exceeds the limit of {}", limit)
+            }
+            Self::TooLarge { limit, .. } => {
+                write!(f, "input size exceeds the limit of {} bytes", limit)
+            }
+            Self::StringTooLong { limit, .. } => {
+                write!(f, "string length exceeds the limit of {} bytes", limit)
+            }
+            Self::NumberTooLong { limit, .. } => {
+                write!(f, "number length exceeds the limit of {} bytes", limit)
+            }
+            Self::NonFiniteNumber { .. } => write!(f, "non-finite number is not allowed"),
+            Self::UnrepresentableNumber { .. } => {
+                write!(f, "number cannot be represented exactly")
+            }
+            Self::InvalidUtf8 { .. } => write!(f, "invalid UTF-8 sequence"),
+            Self::LoneSurrogate { .. } => write!(f, "unpaired surrogate escape"),
+            Self::ByteOrderMark { .. } => write!(f, "byte order mark is not allowed"),
+            Self::InvalidUtf16 { .. } => write!(f, "invalid UTF-16 sequence"),
+            Self::Utf16Unsupported { .. } => {
+                write!(f, "UTF-16 input is not supported in JSON Lines mode")
+            }
         }
     }
+}
 
-    fn skip(&mut self) {
-        let res = self.try_next();
-        assert!(matches!(res, Some(Ok(_))));
+/// A class of repairs that can be enabled or disabled by [`RepairOptions::allow`] and
+/// [`RepairOptions::deny`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// Remove a comma before `]` or `}`.
+    TrailingComma,
+    /// Insert a comma between two members or elements.
+    MissingComma,
+    /// Remove `// line` and `/* block */` comments where whitespace is allowed. See
+    /// [`CommentMode`].
+    Comment,
+    /// Complete the input that ends in the middle of a value. An open string is closed, a
+    /// dangling key or an incomplete literal or number is removed, and the open containers are
+    /// closed.
+    Truncation,
+    /// Convert a single-quoted string to a double-quoted one.
+    SingleQuote,
+    /// Quote an object key consisting of identifier characters, e.g., `{foo: 1}`. ASCII letters
+    /// and digits, `$`, `_` and non-ASCII letters and digits are accepted. Other non-ASCII
+    /// characters, such as smart quotes and a no-break space, are reported as invalid.
+    UnquotedKey,
+    /// Replace a literal of another language, such as `True`, `False` and `None` of Python or
+    /// `undefined` of JavaScript, with a JSON value. See [`RepairOptions::literal`] and
+    /// [`NonFinite`].
+    Literal,
+    /// Remove leading zeros of a number, e.g., `007`.
+    LeadingZero,
+    /// Remove the plus sign of a number, e.g., `+1`.
+    PlusSign,
+    /// Insert `0` before or after a decimal point without digits on that side, e.g., `.5` and `1.`.
+    /// A decimal point without digits on either side is an error.
+    DecimalPoint,
+    /// Insert `0` after an exponent without digits, e.g., `1e` and `1e+`.
+    Exponent,
+    /// Convert a hexadecimal, octal or binary integer, e.g., `0x1F`, `0o17` and `0b11`, to
+    /// decimal.
+    Radix,
+    /// Escape a raw control character in a string, e.g., a line break, as `\n`, `\t`, `\r` or
+    /// `\u00XX`.
+    ControlCharacter,
+    /// Replace an invalid escape sequence in a string. `\xHH` is converted to `\u00HH`, the
+    /// backslash of `\'` is removed, and the backslash of any other invalid escape sequence, e.g.,
+    /// `\a` and `\u12`, is escaped.
+    Escape,
+    /// Replace a byte sequence that is not valid UTF-8 in a string or an unquoted key, including
+    /// an overlong encoding and an encoded surrogate. See [`Utf8Replacement`].
+    InvalidUtf8,
+    /// Replace a `\uXXXX` escape of an unpaired surrogate, e.g., `\uD800` not followed by
+    /// `\uDC00`-`\uDFFF`, with `\uFFFD`.
+    LoneSurrogate,
+    /// Remove a byte order mark at the beginning of the input. See [`RepairOptions::keep_bom`].
+    Bom,
+    /// Remove a JSONP callback, e.g., `callback({...});`, or an assignment, e.g.,
+    /// `var x = {...};`, `window.x = {...};` and `export default {...};`, around the value. If
+    /// [`Rule::Comment`] is allowed, comments after the value are removed with the wrapper.
+    Wrapper,
+    /// Replace a closing bracket that does not match the open container, e.g., `[1, 2}`. If it
+    /// matches an outer container, the closing brackets of the inner ones are inserted instead,
+    /// e.g., `{"a": [1, 2}`. A missing `]` before a key is also inserted, e.g.,
+    /// `{"a": [1, 2, "b": 3}`.
+    MismatchedBracket,
+    /// Insert a missing colon between a key and its value, e.g., `{"a" 1}`.
+    MissingColon,
+    /// Insert a placeholder for a missing value after a colon, e.g., `{"a": }`. See
+    /// [`RepairOptions::placeholder`].
+    MissingValue,
+    /// Complete or remove a key without a colon and a value, e.g., `{"a"}`. See
+    /// [`RepairOptions::lone_key`].
+    LoneKey,
+}
+
+impl Rule {
+    pub const ALL: &[Rule] = &[
+        Self::TrailingComma,
+        Self::MissingComma,
+        Self::Comment,
+        Self::Truncation,
+        Self::SingleQuote,
+        Self::UnquotedKey,
+        Self::Literal,
+        Self::LeadingZero,
+        Self::PlusSign,
+        Self::DecimalPoint,
+        Self::Exponent,
+        Self::Radix,
+        Self::ControlCharacter,
+        Self::Escape,
+        Self::InvalidUtf8,
+        Self::LoneSurrogate,
+        Self::Bom,
+        Self::Wrapper,
+        Self::MismatchedBracket,
+        Self::MissingColon,
+        Self::MissingValue,
+        Self::LoneKey,
+    ];
+
+    /// Returns the name of the rule used in the command-line interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TrailingComma => "trailing-comma",
+            Self::MissingComma => "missing-comma",
+            Self::Comment => "comment",
+            Self::Truncation => "truncation",
+            Self::SingleQuote => "single-quote",
+            Self::UnquotedKey => "unquoted-key",
+            Self::Literal => "literal",
+            Self::LeadingZero => "leading-zero",
+            Self::PlusSign => "plus-sign",
+            Self::DecimalPoint => "decimal-point",
+            Self::Exponent => "exponent",
+            Self::Radix => "radix",
+            Self::ControlCharacter => "control-character",
+            Self::Escape => "escape",
+            Self::InvalidUtf8 => "invalid-utf8",
+            Self::LoneSurrogate => "lone-surrogate",
+            Self::Bom => "bom",
+            Self::Wrapper => "wrapper",
+            Self::MismatchedBracket => "mismatched-bracket",
+            Self::MissingColon => "missing-colon",
+            Self::MissingValue => "missing-value",
+            Self::LoneKey => "lone-key",
+        }
     }
 
-    fn eof(&mut self) -> bool {
-        self.try_next().is_none()
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.name() == name)
     }
 
-    fn try_next(&mut self) -> Option<std::io::Result<u8>>;
-    fn try_peek(&mut self) -> Option<std::io::Result<u8>>;
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
 }
 
-impl<I: Iterator<Item = std::io::Result<u8>>> ByteStream for Peekable<I> {
-    fn try_next(&mut self) -> Option<std::io::Result<u8>> {
-        Iterator::next(self)
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
+}
 
-    fn try_peek(&mut self) -> Option<std::io::Result<u8>> {
-        match Peekable::peek(self) {
-            Some(Ok(c)) => Some(Ok(*c)),
-            Some(Err(_)) => Some(Err(Iterator::next(self)
-                .expect("next() returns some value because peek() returned some value.")
-                .expect_err("next() returns some error because peek() returned some error."))),
-            None => None,
+/// How comments are removed when [`Rule::Comment`] is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommentMode {
+    /// Remove comments entirely.
+    #[default]
+    Strip,
+    /// Replace comments with the same number of spaces, keeping line breaks, so that the byte
+    /// offsets and line numbers of the rest of the input are preserved.
+    Whitespace,
+}
+
+impl CommentMode {
+    /// Returns the byte written in place of the byte `c` of a comment, if any.
+    fn blank(self, c: u8) -> Option<u8> {
+        match self {
+            Self::Strip => None,
+            Self::Whitespace if is_ws(c) => Some(c),
+            Self::Whitespace => Some(b' '),
         }
     }
-}
 
-impl<'input, 'output, I: ByteStream, W: Write> Parser<'input, 'output, I, W> {
-    fn new(input: &'input mut I, output: &'output mut W) -> Self {
-        Self {
-            input,
-            output,
-            repaired: false,
+    /// Returns the fix for the comment between the input offsets `start` and `end`, whose output
+    /// ends at the offset `at`.
+    fn removed(self, start: u64, end: u64, at: u64) -> Fix {
+        let len = match self {
+            Self::Strip => 0,
+            Self::Whitespace => end - start,
+        };
+        Fix {
+            kind: FixKind::CommentRemoved,
+            input: Span::new(start, end),
+            output: Span::new(at - len, at),
         }
     }
+}
+
+/// What an invalid UTF-8 sequence is replaced with when [`Rule::InvalidUtf8`] is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Replacement {
+    /// Replace it with U+FFFD REPLACEMENT CHARACTER.
+    #[default]
+    Char,
+    /// Replace it with the escape sequence `\uFFFD`, keeping the output ASCII.
+    Escape,
+}
+
+/// What `NaN`, `Infinity` and `-Infinity` are replaced with when [`Rule::Literal`] is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinite {
+    /// Replace them with `null`, as `JSON.stringify` of JavaScript does.
+    #[default]
+    Null,
+    /// Replace them with strings, e.g., `"NaN"`.
+    String,
+    /// Report them as [`SyntaxError::NonFiniteNumber`].
+    Error,
+}
+
+/// How a key without a value is repaired when [`Rule::LoneKey`] is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoneKey {
+    /// Complete the member with the placeholder, e.g., `{"a": null}`. See
+    /// [`RepairOptions::placeholder`].
+    #[default]
+    Fill,
+    /// Remove the key.
+    Drop,
+}
+
+/// What [`repair_lines`] does with a line that cannot be repaired. The error is reported in the
+/// result of the line unless the repair stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidLine {
+    /// Stop and report the error.
+    Fail,
+    /// Remove the line, including its line break.
+    Skip,
+    /// Write the line as is.
+    #[default]
+    PassThrough,
+}
+
+/// How the values are written by [`RepairOptions::stream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// Keep the whitespace between the values as is. The comments removed by [`Rule::Comment`]
+    /// are handled as set by [`CommentMode`]. Values separated only by comments, and a number or
+    /// a literal directly followed by another value, are separated by a space, reported as
+    /// [`FixKind::SeparatorReplaced`].
+    Keep,
+    /// Terminate each value by a line break, as in JSON Lines. The whitespace and comments around
+    /// the values are replaced and reported as [`FixKind::SeparatorReplaced`] unless they are a
+    /// single line break after a value. Input without values is written as is.
+    Lines,
+    /// Wrap the values in an array. The whitespace and comments before the first value and after
+    /// the last one are replaced by the brackets and reported as [`FixKind::ArrayWrapped`], and
+    /// those between the values are replaced by commas and reported as
+    /// [`FixKind::SeparatorReplaced`].
+    Array,
+    /// Read and write a JSON text sequence (RFC 7464), where each value is preceded by RS (0x1E)
+    /// and followed by LF. An element that is truncated, cannot be repaired or consists of
+    /// whitespace only is removed with the RS before it and reported as
+    /// [`FixKind::TruncatedElementRemoved`], [`FixKind::InvalidElementRemoved`] or
+    /// [`FixKind::EmptyElementRemoved`].
+    /// An RS added before an element and an LF added after it are reported as
+    /// [`FixKind::RecordSeparatorInserted`] and [`FixKind::LineFeedInserted`].
+    Sequence,
+}
+
+/// Which values are taken by [`RepairOptions::extract`].
+///
+/// Only objects and arrays are taken. A scalar such as `42` or `"text"` outside of them is
+/// regarded as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extract {
+    /// Take the first value and ignore the rest of the input. If a Markdown code block marked as
+    /// JSON, i.e., opened by ```` ```json ````, follows the value, the first value in the block is
+    /// taken instead.
+    First,
+    /// Take every value and write them separated by line breaks.
+    All,
+}
+
+/// The literals replaced by default when [`Rule::Literal`] is allowed.
+const DEFAULT_LITERALS: &[(&str, &str)] = &[
+    ("True", "true"),
+    ("False", "false"),
+    ("None", "null"),
+    ("undefined", "null"),
+];
+
+/// The non-finite numbers and their replacements for [`NonFinite::String`].
+const NON_FINITE_LITERALS: &[(&str, &str)] = &[
+    ("NaN", "\"NaN\""),
+    ("Infinity", "\"Infinity\""),
+    ("-Infinity", "\"-Infinity\""),
+];
+
+enum LiteralLookup<'a> {
+    Unknown,
+    Replace(&'a str),
+    NonFinite,
+}
+
+/// A single JSON value written as is, e.g., by [`RepairOptions::placeholder`]. It is checked when
+/// parsed from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawValue(String);
 
-    fn repaired(&self) -> bool {
-        self.repaired
+impl RawValue {
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
+}
 
-    fn walk_json(&mut self) -> ParserResult {
-        self.walk_element()?;
-        if self.input.eof() {
-            Ok(())
-        } else {
-            Err(SyntaxError::TrailingData.into())
+impl std::str::FromStr for RawValue {
+    type Err = SyntaxError;
+
+    /// Parses `s`, which must be exactly one JSON value with no repairs needed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let options = Rule::ALL
+            .iter()
+            .fold(RepairOptions::new(), |options, &rule| options.deny(rule));
+        match repair_with(&options, s.as_bytes(), std::io::sink()) {
+            Ok(_) => Ok(Self(s.to_owned())),
+            Err(RepairErr::Invalid(err)) => Err(err),
+            Err(RepairErr::IoErr(err)) => unreachable!("writing to a sink failed: {err}"),
         }
     }
+}
 
-    fn walk_value(&mut self) -> ParserResult {
-        let c = self.input.peek()??;
+/// A spelling replaced by [`Rule::Literal`], e.g., `nil` or `-Inf`. See
+/// [`RepairOptions::literal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralSpelling(String);
 
-        match c {
-            b'n' => {
-                self.input.skip(); // => n
-                self.output.write_all(b"n")?;
-                self.walk_char_of(b'u')?;
-                self.walk_char_of(b'l')?;
-                self.walk_char_of(b'l')?;
-                Ok(())
-            }
-            b't' => {
-                self.input.skip(); // => t
-                self.output.write_all(b"t")?;
-                self.walk_char_of(b'r')?;
-                self.walk_char_of(b'u')?;
-                self.walk_char_of(b'e')?;
-                Ok(())
-            }
-            b'f' => {
-                self.input.skip(); // => f
-                self.output.write_all(b"f")?;
-                self.walk_char_of(b'a')?;
-                self.walk_char_of(b'l')?;
-                self.walk_char_of(b's')?;
-                self.walk_char_of(b'e')?;
-                Ok(())
+impl LiteralSpelling {
+    /// Checks `spelling`, which must consist of ASCII letters, digits, `$` and `_`, optionally
+    /// preceded by `-`, and must not start with a digit. Returns `None` otherwise, as such a
+    /// spelling is never matched.
+    pub fn new(spelling: impl Into<String>) -> Option<Self> {
+        let spelling = spelling.into();
+        let word = spelling.strip_prefix('-').unwrap_or(&spelling).as_bytes();
+        match word {
+            [c, rest @ ..] if is_word_start(*c) && rest.iter().all(|&c| is_word_char(c)) => {
+                Some(Self(spelling))
             }
-            b'{' => self.walk_object(),
-            b'[' => self.walk_array(),
-            b'"' => self.walk_string(),
-            b'-' => self.walk_number(),
-            c if c.is_ascii_digit() => self.walk_number(),
-            _ => Err(SyntaxError::InvalidValue.into()),
+            _ => None,
         }
     }
 
-    fn walk_object(&mut self) -> ParserResult {
-        self.output.write_all(b"{")?;
-        self.input.skip(); // => {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
 
-        self.walk_ws()?;
+/// Options to configure [`repair_with`].
+///
+/// All limits are disabled by default. [`Rule::TrailingComma`] and [`Rule::MissingComma`] are
+/// allowed by default and the other rules are not. When a repair is denied, the input it would
+/// fix is reported as a [`SyntaxError`].
+#[derive(Debug, Clone)]
+pub struct RepairOptions {
+    rules: u32,
+    comment_mode: CommentMode,
+    utf8_replacement: Utf8Replacement,
+    literals: Vec<(String, RawValue)>,
+    default_literals: bool,
+    non_finite: NonFinite,
+    placeholder: RawValue,
+    lone_key: LoneKey,
+    check_surrogates: bool,
+    keep_bom: bool,
+    extract: Option<Extract>,
+    stream: Option<Stream>,
+    invalid_line: InvalidLine,
+    max_depth: Option<usize>,
+    max_size: Option<u64>,
+    max_string_length: Option<u64>,
+    max_number_length: Option<u64>,
+}
 
-        // members_opt
-        let first = self.input.peek()??;
-        if first == b'"' {
-            self.walk_members()?;
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            rules: Rule::TrailingComma.bit() | Rule::MissingComma.bit(),
+            comment_mode: CommentMode::default(),
+            utf8_replacement: Utf8Replacement::default(),
+            literals: Vec::new(),
+            default_literals: true,
+            non_finite: NonFinite::default(),
+            placeholder: RawValue("null".to_owned()),
+            lone_key: LoneKey::default(),
+            check_surrogates: false,
+            keep_bom: false,
+            extract: None,
+            stream: None,
+            invalid_line: InvalidLine::default(),
+            max_depth: None,
+            max_size: None,
+            max_string_length: None,
+            max_number_length: None,
         }
+    }
+}
 
-        // trailing_comma_opt
-        let maybe_comma = self.input.peek()??;
-        if maybe_comma == b',' {
-            self.repaired = true;
-            self.input.skip();
-            self.walk_ws()?;
-        }
+impl RepairOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        self.walk_char_of(b'}')
+    /// Enables the given repair.
+    pub fn allow(mut self, rule: Rule) -> Self {
+        self.rules |= rule.bit();
+        self
     }
 
-    fn walk_members(&mut self) -> ParserResult {
-        loop {
-            self.walk_member()?;
+    /// Disables the given repair.
+    pub fn deny(mut self, rule: Rule) -> Self {
+        self.rules &= !rule.bit();
+        self
+    }
 
-            let mut ws = Vec::with_capacity(1024);
-            self.walk_ws_with_buf(&mut ws)?;
+    pub fn is_allowed(&self, rule: Rule) -> bool {
+        self.rules & rule.bit() != 0
+    }
 
-            let next = self.input.peek()??;
-            match next {
-                b'}' => {
-                    self.output.write_all(&ws)?;
-                    return Ok(());
-                }
-                b',' => {
-                    self.output.write_all(&ws)?;
-                    // Re-use the memory buffer to avoid another allocation.
-                    ws.clear();
+    /// Sets how comments are removed. This has no effect unless [`Rule::Comment`] is allowed.
+    pub fn comment_mode(mut self, mode: CommentMode) -> Self {
+        self.comment_mode = mode;
+        self
+    }
 
-                    self.input.skip();
+    /// Sets what an invalid UTF-8 sequence is replaced with. This has no effect unless
+    /// [`Rule::InvalidUtf8`] is allowed.
+    pub fn utf8_replacement(mut self, replacement: Utf8Replacement) -> Self {
+        self.utf8_replacement = replacement;
+        self
+    }
 
-                    self.walk_ws_with_buf(&mut ws)?;
+    /// Replaces `spelling` with `replacement` when [`Rule::Literal`] is allowed.
+    ///
+    /// This overrides the default replacements of `True`, `False`, `None`, `undefined`, `NaN`,
+    /// `Infinity` and `-Infinity`.
+    pub fn literal(mut self, spelling: LiteralSpelling, replacement: RawValue) -> Self {
+        self.literals.push((spelling.0, replacement));
+        self
+    }
 
-                    let c = self.input.peek()??;
-                    match c {
-                        b'}' => {
-                            self.repaired = true;
-                            self.output.write_all(&ws)?;
-                            return Ok(());
-                        }
-                        _ => {
-                            self.output.write_all(b",")?;
-                            self.output.write_all(&ws)?;
-                        }
-                    }
-                }
-                _ => {
-                    self.repaired = true;
-                    self.output.write_all(b",")?;
-                    self.output.write_all(&ws)?;
-                }
-            }
-        }
+    /// Sets whether `True`, `False`, `None` and `undefined` are replaced by default when
+    /// [`Rule::Literal`] is allowed. When disabled, only the literals added by
+    /// [`Self::literal`] and the non-finite numbers set by [`Self::non_finite`] are replaced. This
+    /// is enabled by default.
+    pub fn default_literals(mut self, enabled: bool) -> Self {
+        self.default_literals = enabled;
+        self
     }
 
-    fn walk_member(&mut self) -> ParserResult {
-        self.walk_string()?;
-        self.walk_ws()?;
-        self.walk_char_of(b':')?;
-        self.walk_ws()?;
-        self.walk_value()
+    /// Sets what `NaN`, `Infinity` and `-Infinity` are replaced with. This has no effect unless
+    /// [`Rule::Literal`] is allowed.
+    pub fn non_finite(mut self, target: NonFinite) -> Self {
+        self.non_finite = target;
+        self
     }
 
-    fn walk_array(&mut self) -> ParserResult {
-        self.output.write_all(b"[")?;
-        self.input.skip(); // => [
+    /// Sets what is inserted for a missing value by [`Rule::MissingValue`] and [`Rule::LoneKey`].
+    /// The default is `null`.
+    pub fn placeholder(mut self, value: RawValue) -> Self {
+        self.placeholder = value;
+        self
+    }
 
-        self.walk_ws()?;
+    /// Sets how a key without a value is repaired. This has no effect unless [`Rule::LoneKey`] is
+    /// allowed.
+    pub fn lone_key(mut self, mode: LoneKey) -> Self {
+        self.lone_key = mode;
+        self
+    }
 
-        // elements_opt
-        let first = self.input.peek()??;
-        if first != b',' && first != b']' {
-            self.walk_elements()?;
+    fn find_literal(&self, spelling: &[u8]) -> LiteralLookup<'_> {
+        let find = |table: &'static [(&str, &str)]| {
+            table
+                .iter()
+                .find(|(s, _)| s.as_bytes() == spelling)
+                .map(|(_, r)| *r)
+        };
+        // The later ones take precedence.
+        if let Some((_, replacement)) = self
+            .literals
+            .iter()
+            .rev()
+            .find(|(s, _)| s.as_bytes() == spelling)
+        {
+            return LiteralLookup::Replace(replacement.as_str());
+        }
+        if self.default_literals
+            && let Some(replacement) = find(DEFAULT_LITERALS)
+        {
+            return LiteralLookup::Replace(replacement);
         }
+        match (find(NON_FINITE_LITERALS), self.non_finite) {
+            (None, _) => LiteralLookup::Unknown,
+            (Some(_), NonFinite::Null) => LiteralLookup::Replace("null"),
+            (Some(replacement), NonFinite::String) => LiteralLookup::Replace(replacement),
+            (Some(_), NonFinite::Error) => LiteralLookup::NonFinite,
+        }
+    }
 
-        // trailing_comma_opt
-        let maybe_comma = self.input.peek()??;
-        if maybe_comma == b',' {
-            self.repaired = true;
-            self.input.skip();
-            self.walk_ws()?;
+    /// Reports [`SyntaxError::TooLarge`] if a byte at `pos` would exceed [`Self::max_size`].
+    fn check_size(&self, pos: Position) -> Result<(), SyntaxError> {
+        match self.max_size {
+            Some(limit) if pos.offset >= limit => Err(SyntaxError::TooLarge { pos, limit }),
+            _ => Ok(()),
         }
+    }
 
-        self.walk_char_of(b']')
+    /// Returns the length of the longest literal that can be replaced.
+    fn max_literal_len(&self) -> usize {
+        DEFAULT_LITERALS
+            .iter()
+            .chain(NON_FINITE_LITERALS)
+            .map(|(s, _)| s.len())
+            .chain(self.literals.iter().map(|(s, _)| s.len()))
+            .max()
+            .unwrap_or(0)
     }
 
-    fn walk_elements(&mut self) -> ParserResult {
-        loop {
-            self.walk_value()?;
+    /// Reports an unpaired surrogate escape as [`SyntaxError::LoneSurrogate`], as I-JSON (RFC
+    /// 7493) requires. When [`Rule::LoneSurrogate`] is allowed, it is repaired instead.
+    pub fn check_surrogates(mut self, enabled: bool) -> Self {
+        self.check_surrogates = enabled;
+        self
+    }
 
-            let mut ws = Vec::with_capacity(1024);
-            self.walk_ws_with_buf(&mut ws)?;
+    /// Writes a byte order mark at the beginning of the input to the output as is, instead of
+    /// removing it by [`Rule::Bom`] or reporting it as [`SyntaxError::ByteOrderMark`].
+    ///
+    /// UTF-16LE and UTF-16BE input with a byte order mark is transcoded to UTF-8, and the byte
+    /// order mark is handled in the same way as that of UTF-8. The transcoding is reported as
+    /// [`FixKind::Utf16Transcoded`] with the span of the byte order mark, whether it is kept or
+    /// not. The positions in such input are the byte offsets in the transcoded UTF-8.
+    pub fn keep_bom(mut self, enabled: bool) -> Self {
+        self.keep_bom = enabled;
+        self
+    }
 
-            let next = self.input.peek()??;
-            match next {
-                b']' => {
-                    self.output.write_all(&ws)?;
-                    return Ok(());
-                }
-                b',' => {
-                    self.output.write_all(&ws)?;
-                    // Re-use the memory buffer to avoid another allocation.
-                    ws.clear();
+    /// Extracts JSON from arbitrary text, e.g., the output of an LLM, instead of parsing the
+    /// whole input as JSON.
+    ///
+    /// Every object or array in the text, including one in a Markdown code block, is a candidate.
+    /// [`Extract::First`] prefers a value in a code block marked as JSON to the values before it.
+    /// A candidate that cannot be parsed even with the allowed repairs is skipped, and the objects
+    /// and arrays in it become candidates in turn. To keep the time linear, the input scanned
+    /// again in this way is limited to four times the input size. The text around the taken values
+    /// is reported as [`FixKind::TextRemoved`], even if it is whitespace, and the spans of the
+    /// values are reported by [`RepairReport::extracted`].
+    pub fn extract(mut self, mode: Extract) -> Self {
+        self.extract = Some(mode);
+        self
+    }
 
-                    self.input.skip();
+    /// Reads a sequence of top-level values, e.g., `{...}{...}` or `1 2 3`, instead of reporting
+    /// the values after the first one as [`SyntaxError::TrailingData`]. See also [`repair_stream`].
+    /// A number directly followed by `.`, a digit or a letter, e.g., `1.2.3`, is still reported as
+    /// [`SyntaxError::TrailingData`] rather than split into values.
+    ///
+    /// [`Rule::Wrapper`] is not applied to the values, and this has no effect if
+    /// [`RepairOptions::extract`] is set.
+    pub fn stream(mut self, mode: Stream) -> Self {
+        self.stream = Some(mode);
+        self
+    }
 
-                    self.walk_ws_with_buf(&mut ws)?;
+    /// Sets what [`repair_lines`] does with a line that cannot be repaired.
+    pub fn invalid_line(mut self, mode: InvalidLine) -> Self {
+        self.invalid_line = mode;
+        self
+    }
 
-                    let c = self.input.peek()??;
-                    match c {
-                        b']' => {
-                            self.repaired = true;
-                            self.output.write_all(&ws)?;
-                            return Ok(());
-                        }
-                        _ => {
-                            self.output.write_all(b",")?;
-                            self.output.write_all(&ws)?;
-                        }
-                    }
-                }
-                _ => {
-                    self.repaired = true;
-                    self.output.write_all(b",")?;
-                    self.output.write_all(&ws)?;
-                }
-            }
-        }
+    /// Sets the maximum number of nested objects and arrays.
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.max_depth = Some(limit);
+        self
     }
 
-    fn walk_element(&mut self) -> ParserResult {
-        self.walk_ws()?;
-        self.walk_value()?;
-        self.walk_ws()
+    /// Sets the maximum size of the input in bytes. The size of UTF-16 input is counted in bytes
+    /// after it is transcoded to UTF-8, as [`Position::offset`] is.
+    pub fn max_size(mut self, limit: u64) -> Self {
+        self.max_size = Some(limit);
+        self
     }
 
-    fn walk_string(&mut self) -> ParserResult {
-        self.output.write_all(b"\"")?;
-        self.input.skip(); // => "
-        loop {
-            match self.input.next()?? {
-                b'"' => break,
-                b'\\' => {
-                    self.walk_escape()?;
-                }
-                c if c < 0x20 => {
-                    // A raw byte less than 0x20 cannot be embedded in string.
-                    return Err(SyntaxError::InvalidValue.into());
-                }
-                c => {
-                    self.output.write_all(&[c])?;
-                }
-            }
-        }
-        self.output.write_all(b"\"")?;
-        Ok(())
+    /// Sets the maximum length of a string in bytes, excluding the quotes. Escape sequences are
+    /// counted as they appear in the input.
+    pub fn max_string_length(mut self, limit: u64) -> Self {
+        self.max_string_length = Some(limit);
+        self
     }
 
-    fn walk_escape(&mut self) -> ParserResult {
-        let c = self.input.next()??;
-        match c {
-            b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {
-                self.output.write_all(&[b'\\', c])?;
-            }
-            b'u' => {
-                let u1 = self.input.next()??;
-                let u2 = self.input.next()??;
-                let u3 = self.input.next()??;
-                let u4 = self.input.next()??;
-                if !u1.is_ascii_hexdigit()
-                    || !u2.is_ascii_hexdigit()
-                    || !u3.is_ascii_hexdigit()
-                    || !u4.is_ascii_hexdigit()
-                {
-                    return Err(SyntaxError::InvalidValue.into());
-                }
-                self.output.write_all(&[b'\\', b'u', u1, u2, u3, u4])?;
-            }
-            _ => return Err(SyntaxError::InvalidValue.into()),
+    /// Sets the maximum length of a number in bytes.
+    pub fn max_number_length(mut self, limit: u64) -> Self {
+        self.max_number_length = Some(limit);
+        self
+    }
+}
+
+pub fn repair(r: impl Read, w: impl Write) -> RepairResult {
+    repair_with(&RepairOptions::default(), r, w)
+}
+
+pub fn repair_with(options: &RepairOptions, r: impl Read, w: impl Write) -> RepairResult {
+    let mut d = Driver::new(options, w);
+    read_chunks(r, |buf| d.feed(buf))?;
+    let (_, result) = d.finish()?;
+    Ok(result)
+}
+
+/// Repairs JSON read from an async reader and writes it to an async writer without blocking the
+/// executor.
+#[cfg(feature = "tokio")]
+pub async fn repair_async(
+    r: impl tokio::io::AsyncRead + Unpin,
+    w: impl tokio::io::AsyncWrite + Unpin,
+) -> RepairResult {
+    repair_with_async(&RepairOptions::default(), r, w).await
+}
+
+/// Repairs JSON read from an async reader as [`repair_with`] does. The input is processed chunk by
+/// chunk, and the output is written as soon as it is final.
+#[cfg(feature = "tokio")]
+pub async fn repair_with_async(
+    options: &RepairOptions,
+    mut r: impl tokio::io::AsyncRead + Unpin,
+    mut w: impl tokio::io::AsyncWrite + Unpin,
+) -> RepairResult {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut output = Vec::new();
+    let mut d = Driver::new(options, &mut output);
+    let mut buf = vec![0; 8 * 1024];
+    let result = loop {
+        let n = match r.read(&mut buf).await {
+            Ok(n) => n,
+            Err(err) => break Err(err.into()),
+        };
+        if n == 0 {
+            break d.finish().map(|(_, result)| result);
+        }
+        if let Err(err) = d.feed(&buf[..n]) {
+            break Err(err);
+        }
+        w.write_all(&std::mem::take(*d.output_mut())).await?;
+    };
+    // Write the output before the error as the blocking version does.
+    w.write_all(&output).await?;
+    w.flush().await?;
+    result
+}
+
+/// Repairs JSON pushed in chunks as [`repair_with`] does, e.g., the messages of a WebSocket or a
+/// streaming response of an LLM.
+///
+/// The output is returned as soon as it is final. An incomplete token is kept until the following
+/// chunks complete it, and so is an incomplete member or element when [`Rule::Truncation`] is
+/// allowed because it may be discarded at the end of input.
+pub struct Repairer {
+    driver: Driver<Vec<u8>>,
+}
+
+impl Repairer {
+    pub fn new(options: &RepairOptions) -> Self {
+        Self {
+            driver: Driver::new(options, Vec::new()),
         }
-        Ok(())
     }
 
-    fn walk_number(&mut self) -> ParserResult {
-        self.walk_integer()?;
-        self.walk_fraction()?;
-        self.walk_exponent()
+    /// Feeds the next chunk of the input and returns the output that has become available.
+    pub fn feed(&mut self, buf: &[u8]) -> Result<Vec<u8>, RepairErr> {
+        self.driver.feed(buf)?;
+        Ok(std::mem::take(self.driver.output_mut()))
     }
 
-    fn walk_integer(&mut self) -> ParserResult {
-        let first = self.input.next()??;
-        match first {
-            b'-' => {
-                self.output.write_all(b"-")?;
-                return self.walk_integer();
-            }
-            b'0' => {
-                self.output.write_all(b"0")?;
-                return Ok(());
-            }
-            b'1' | b'2' | b'3' | b'4' | b'5' | b'6' | b'7' | b'8' | b'9' => {
-                self.output.write_all(&[first])?;
-                loop {
-                    let Some(c) = self.input.try_peek() else {
-                        return Ok(());
-                    };
-                    let c = c?;
-                    if c.is_ascii_digit() {
-                        self.output.write_all(&[c])?;
-                        self.input.skip();
-                    } else {
-                        break;
-                    }
-                }
-            }
-            _ => return Err(SyntaxError::InvalidValue.into()),
-        }
-        Ok(())
+    /// Processes the end of input and returns the rest of the output with the result.
+    pub fn finish(self) -> Result<(Vec<u8>, RepairOk), RepairErr> {
+        self.driver.finish()
     }
+}
 
-    fn walk_digits(&mut self) -> ParserResult {
-        let mut has_digit = false;
-        while let Some(c) = self.input.try_peek() {
-            let c = c?;
-            if c.is_ascii_digit() {
-                self.output.write_all(&[c])?;
-                self.input.skip();
-                has_digit = true;
-            } else {
-                break;
+/// Feeds the input to the driver selected by [`RepairOptions`].
+enum Driver<W: Write> {
+    Parser(Parser<W>),
+    Unwrapper(Unwrapper<W>),
+    Extractor(Extractor<W>),
+    Stream(StreamRepairer<StreamWriter<W>>),
+    Sequence(SeqRepairer<StreamWriter<W>>),
+}
+
+impl<W: Write> Driver<W> {
+    fn new(options: &RepairOptions, w: W) -> Self {
+        let options = options.clone();
+        if let Some(mode) = options.extract {
+            return Self::Extractor(Extractor::new(options, mode, w));
+        }
+        match options.stream {
+            Some(Stream::Sequence) => {
+                return Self::Sequence(SeqRepairer::new(
+                    options,
+                    StreamWriter::new(w, Stream::Sequence),
+                ));
+            }
+            Some(mode) => {
+                return Self::Stream(StreamRepairer::new(options, StreamWriter::new(w, mode)));
             }
+            None => {}
         }
-        if has_digit {
-            Ok(())
-        } else {
-            Err(SyntaxError::InvalidValue.into())
+        if options.is_allowed(Rule::Wrapper) {
+            return Self::Unwrapper(Unwrapper::new(options, w));
         }
+        Self::Parser(Parser::new(options, w))
     }
 
-    fn walk_fraction(&mut self) -> ParserResult {
-        let Some(first) = self.input.try_peek() else {
-            return Ok(());
-        };
-        let first = first?;
-        if first != b'.' {
-            return Ok(());
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        match self {
+            Self::Parser(p) => p.feed(buf),
+            Self::Unwrapper(u) => u.feed(buf),
+            Self::Extractor(e) => e.feed(buf),
+            Self::Stream(s) => s.feed(buf),
+            Self::Sequence(s) => s.feed(buf),
         }
-        self.output.write_all(b".")?;
-        self.input.skip();
-        self.walk_digits()
     }
 
-    fn walk_exponent(&mut self) -> ParserResult {
-        let Some(first) = self.input.try_peek() else {
-            return Ok(());
-        };
-        let first = first?;
-        if first != b'e' && first != b'E' {
-            return Ok(());
+    /// Returns the writer. The output held back by the parser is not written to it yet.
+    fn output_mut(&mut self) -> &mut W {
+        match self {
+            Self::Parser(p) => p.output.get_mut(),
+            Self::Unwrapper(u) => u.parser.output.get_mut(),
+            Self::Extractor(e) => &mut e.output,
+            Self::Stream(s) => &mut s.sink.output,
+            Self::Sequence(s) => &mut s.sink.output,
         }
-        self.output.write_all(&[first])?;
-        self.input.skip();
-        self.walk_sign()?;
-        self.walk_digits()
     }
 
-    fn walk_sign(&mut self) -> ParserResult {
-        let c = self.input.peek()??;
-        if c == b'+' || c == b'-' {
-            self.output.write_all(&[c])?;
-            self.input.skip();
+    fn finish(self) -> Result<(W, RepairOk), RepairErr> {
+        match self {
+            Self::Parser(p) => p.finish(),
+            Self::Unwrapper(u) => u.finish(),
+            Self::Extractor(e) => e.finish(),
+            Self::Stream(s) => {
+                let (sink, rest) = s.finish()?;
+                sink.finish(rest)
+            }
+            Self::Sequence(s) => {
+                let (sink, rest) = s.finish()?;
+                sink.finish(rest)
+            }
         }
-        Ok(())
     }
+}
 
-    fn walk_ws(&mut self) -> ParserResult {
-        Self::do_walk_ws(self.input, self.output)
+/// Repairs a sequence of top-level values as [`RepairOptions::stream`] does, calling `f` for each
+/// value instead of writing them. The values are not buffered beyond the one being repaired.
+///
+/// In [`Stream::Sequence`] mode, a removed element is passed with an empty output and the fix that
+/// removed it. A byte order mark is checked but not passed to `f`.
+///
+/// An error returned by `f` stops the repair and is returned as [`RepairErr::IoErr`].
+pub fn repair_stream(
+    options: &RepairOptions,
+    r: impl Read,
+    mut f: impl FnMut(StreamValue) -> std::io::Result<()>,
+) -> Result<(), RepairErr> {
+    let f = |_: Separator, value: StreamValue| f(value);
+    if options.stream == Some(Stream::Sequence) {
+        let mut s = SeqRepairer::new(options.clone(), f);
+        read_chunks(r, |buf| s.feed(buf))?;
+        let _ = s.finish()?;
+    } else {
+        let mut s = StreamRepairer::new(options.clone(), f);
+        read_chunks(r, |buf| s.feed(buf))?;
+        let _ = s.finish()?;
     }
+    Ok(())
+}
 
-    fn walk_ws_with_buf(&mut self, buf: &mut Vec<u8>) -> ParserResult {
-        Self::do_walk_ws(self.input, buf)
-    }
+/// The whitespace and comments before a value in [`StreamRepairer`], or the RS before an element
+/// in [`SeqRepairer`].
+struct Separator {
+    input: Span,
+    /// The separator with its comments removed as set by [`RepairOptions::comment_mode`].
+    output: Vec<u8>,
+    /// The fixes for the comments. Their output spans are relative to `output`.
+    fixes: Vec<Fix>,
+}
 
-    fn do_walk_ws<Output: Write>(input: &mut I, output: &mut Output) -> ParserResult {
-        loop {
-            let Some(c) = input.try_peek() else {
-                return Ok(());
-            };
-            let c = c?;
-            match c {
-                0x09 | 0x0A | 0x0D | 0x20 => {
-                    output.write_all(&[c])?;
-                    input.skip();
-                }
-                _ => return Ok(()),
-            }
+impl Separator {
+    /// Returns an empty separator at the input offset `start`, which grows as the input is read.
+    fn new(start: u64) -> Self {
+        Self {
+            input: Span::empty(start),
+            output: Vec::new(),
+            fixes: Vec::new(),
         }
     }
+}
 
-    fn walk_char_of(&mut self, expected: u8) -> ParserResult {
-        let c = self.input.next()??;
-        if c != expected {
-            return Err(SyntaxError::InvalidValue.into());
-        }
-        self.output.write_all(&[c])?;
+/// Receives the values repaired by [`StreamRepairer`] and [`SeqRepairer`] with the separators
+/// before them.
+trait ValueSink {
+    fn value(&mut self, separator: Separator, value: StreamValue) -> std::io::Result<()>;
+
+    /// Receives the fixes for the byte order mark at the beginning of the input, which is kept
+    /// if `keep` is true. The mark is dropped by default.
+    fn bom(&mut self, _keep: bool, _fixes: Vec<Fix>) -> std::io::Result<()> {
         Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    fn repair(input: &str) -> (super::RepairResult, String) {
-        let mut output = Vec::new();
-        let result = super::repair(input.as_bytes(), &mut output);
-        (result, String::from_utf8(output).unwrap())
+impl<F: FnMut(Separator, StreamValue) -> std::io::Result<()>> ValueSink for F {
+    fn value(&mut self, separator: Separator, value: StreamValue) -> std::io::Result<()> {
+        self(separator, value)
     }
+}
 
-    #[test]
-    fn test_repair_invalid() {
-        assert!(repair(r#"foo"#).0.is_err());
-        assert!(repair(r#"{{}"#).0.is_err());
-        assert!(repair(r#"[]]"#).0.is_err());
-        assert!(repair(r#"[,,]"#).0.is_err());
+/// Writes the values of [`RepairOptions::stream`] in the given mode.
+struct StreamWriter<W: Write> {
+    output: W,
+    mode: Stream,
+    offset: u64,
+    /// The number of values written so far.
+    count: u64,
+    /// Whether the last value written is a number or a literal, which the next value may
+    /// continue without a separator.
+    scalar: bool,
+    fixes: Vec<Fix>,
+}
+
+impl<W: Write> StreamWriter<W> {
+    fn new(output: W, mode: Stream) -> Self {
+        Self {
+            output,
+            mode,
+            offset: 0,
+            count: 0,
+            scalar: false,
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Writes `buf` in place of `separator`, and records a fix of `kind` unless they are the
+    /// same. The comments in a replaced separator are replaced as well.
+    fn replace(&mut self, kind: FixKind, separator: Separator, buf: &[u8]) -> std::io::Result<()> {
+        let at = self.offset;
+        if separator.output == buf {
+            self.extend_fixes(separator.fixes);
+        } else {
+            self.fixes.push(Fix {
+                kind,
+                input: separator.input,
+                output: Span::new(at, at + buf.len() as u64),
+            });
+            self.fixes
+                .extend(separator.fixes.into_iter().map(|mut fix| {
+                    fix.output = Span::empty(at);
+                    fix
+                }));
+        }
+        self.write(buf)
+    }
+
+    /// Writes `separator` as is.
+    fn keep(&mut self, separator: Separator) -> std::io::Result<()> {
+        self.extend_fixes(separator.fixes);
+        self.write(&separator.output)
+    }
+
+    /// Records `fixes` for the output to be written next.
+    fn extend_fixes(&mut self, fixes: Vec<Fix>) {
+        let offset = self.offset;
+        self.fixes.extend(fixes.into_iter().map(|mut fix| {
+            fix.output = Span::new(offset + fix.output.start, offset + fix.output.end);
+            fix
+        }));
+    }
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.output.write_all(buf)?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the separator after the last value and returns the result.
+    fn finish(mut self, rest: Separator) -> Result<(W, RepairOk), RepairErr> {
+        match self.mode {
+            Stream::Keep => self.keep(rest)?,
+            Stream::Lines if self.count == 0 => self.keep(rest)?,
+            Stream::Lines => self.replace(FixKind::SeparatorReplaced, rest, b"\n")?,
+            Stream::Sequence => {}
+            Stream::Array if self.count == 0 => self.replace(FixKind::ArrayWrapped, rest, b"[]")?,
+            Stream::Array => self.replace(FixKind::ArrayWrapped, rest, b"]")?,
+        }
+        let result = RepairOk::from_fixes(self.fixes);
+        Ok((self.output, result))
+    }
+}
+
+impl<W: Write> ValueSink for StreamWriter<W> {
+    fn bom(&mut self, keep: bool, fixes: Vec<Fix>) -> std::io::Result<()> {
+        // The mark precedes everything else, including the opening bracket of an array.
+        self.fixes.extend(fixes);
+        if keep {
+            self.write(BOM.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, separator: Separator, value: StreamValue) -> std::io::Result<()> {
+        // A removed sequence element has no output.
+        let removed = value.output.is_empty();
+        match self.mode {
+            // Values separated only by comments are kept apart, e.g., `1/**/2`, and so are a
+            // repaired scalar and the next value, e.g., `.5.5`.
+            Stream::Keep
+                if self.count > 0
+                    && separator.output.is_empty()
+                    && (self.scalar || !separator.fixes.is_empty()) =>
+            {
+                self.replace(FixKind::SeparatorReplaced, separator, b" ")?
+            }
+            Stream::Keep => self.keep(separator)?,
+            Stream::Lines if self.count > 0 => {
+                self.replace(FixKind::SeparatorReplaced, separator, b"\n")?
+            }
+            Stream::Lines => self.replace(FixKind::SeparatorReplaced, separator, b"")?,
+            Stream::Array if self.count > 0 => {
+                self.replace(FixKind::SeparatorReplaced, separator, b",")?
+            }
+            Stream::Array => self.replace(FixKind::ArrayWrapped, separator, b"[")?,
+            Stream::Sequence if !removed => {
+                self.replace(FixKind::RecordSeparatorInserted, separator, b"\x1e")?
+            }
+            Stream::Sequence => {}
+        }
+        if let RepairOk::Repaired(report) = value.result {
+            self.extend_fixes(report.fixes);
+        }
+        self.write(value.output)?;
+        self.scalar = !matches!(value.output.first(), None | Some(b'{' | b'[' | b'"'));
+        if self.mode == Stream::Sequence && !removed && !value.output.ends_with(b"\n") {
+            let end = Separator::new(value.input.end);
+            self.replace(FixKind::LineFeedInserted, end, b"\n")?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Repairs JSON Lines (NDJSON), where each line is a JSON value. Each line is repaired
+/// independently and the line breaks are kept. Blank lines are written as is and have no result.
+/// A line of comments removed by [`Rule::Comment`] has no value either, and is written without
+/// the comments.
+///
+/// `f` is called with the result of each line after the line is written, so that the results are
+/// not kept in memory. An error returned by `f` stops the repair and is returned as
+/// [`RepairErr::IoErr`].
+///
+/// The input must be UTF-8; UTF-16 input is reported as [`SyntaxError::Utf16Unsupported`].
+/// [`RepairOptions::extract`] and [`Rule::Wrapper`] are not applied to
+/// the lines. A line that cannot be repaired is handled as set by [`RepairOptions::invalid_line`].
+pub fn repair_lines(
+    options: &RepairOptions,
+    r: impl Read,
+    w: impl Write,
+    f: impl FnMut(LineResult) -> std::io::Result<()>,
+) -> Result<(), RepairErr> {
+    let mut l = LineRepairer::new(options.clone(), w, f);
+    read_chunks(r, |buf| l.feed(buf))?;
+    l.finish()
+}
+
+/// Reads `r` to the end, passing each chunk to `f`.
+fn read_chunks(r: impl Read, mut f: impl FnMut(&[u8]) -> ParserResult) -> ParserResult {
+    let mut r = BufReader::new(r);
+    loop {
+        let buf = match r.fill_buf() {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if buf.is_empty() {
+            return Ok(());
+        }
+        f(buf)?;
+        let n = buf.len();
+        r.consume(n);
+    }
+}
+
+/// Extracts JSON values from arbitrary text for [`RepairOptions::extract`].
+///
+/// The input is decoded first, so that a byte order mark or UTF-16 input is handled as [`Parser`]
+/// does. Every `{` or `[` in the text starts a candidate value, which is parsed by its own
+/// [`Parser`].
+/// If the candidate turns out to be invalid, its input is scanned again from the next `{` or `[`
+/// in it. The bytes scanned again are limited to [`MAX_RESCAN_FACTOR`] times the input size, so
+/// that the extraction takes linear time; the rest of a candidate rejected beyond the limit is
+/// regarded as text.
+///
+/// For [`Extract::First`], a value found before any [`JSON_FENCE`] is held back until the end of
+/// the input, and it is replaced by the first value after the fence if there is one.
+struct Extractor<W: Write> {
+    options: RepairOptions,
+    mode: Extract,
+    output: W,
+    output_offset: u64,
+    /// The position at the end of the input read so far.
+    end: Position,
+    /// The input offset of the next byte to scan.
+    offset: u64,
+    /// The bytes to scan, including the ones to scan again after a candidate is rejected.
+    queue: VecDeque<u8>,
+    /// The parser of the current candidate and the input offset where it started.
+    candidate: Option<(Parser<Vec<u8>>, u64)>,
+    /// The bytes fed to the current candidate.
+    candidate_input: Vec<u8>,
+    /// The number of bytes scanned again after rejected candidates.
+    rescanned: u64,
+    /// The input offset where the text after the last extracted value started.
+    text_start: u64,
+    /// The number of bytes of [`JSON_FENCE`] matched by the text just scanned.
+    fence: usize,
+    /// Whether [`JSON_FENCE`] has been found in the text.
+    fenced: bool,
+    /// The value held back for [`Extract::First`]: its input span, its output and its fixes.
+    held: Option<(Span, Vec<u8>, Vec<Fix>)>,
+    fixes: Vec<Fix>,
+    extracted: Vec<Span>,
+    decoder: Decoder,
+}
+
+impl<W: Write> Extractor<W> {
+    fn new(options: RepairOptions, mode: Extract, output: W) -> Self {
+        Self {
+            options,
+            mode,
+            output,
+            output_offset: 0,
+            end: Position::start(),
+            offset: 0,
+            queue: VecDeque::new(),
+            candidate: None,
+            candidate_input: Vec::new(),
+            rescanned: 0,
+            text_start: 0,
+            fence: 0,
+            fenced: false,
+            held: None,
+            fixes: Vec::new(),
+            extracted: Vec::new(),
+            decoder: Decoder::new(),
+        }
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for &c in buf {
+            let decoded = self.decoder.decode(c);
+            self.feed_decoded(decoded)?;
+        }
+        self.drain()
+    }
+
+    fn feed_decoded(&mut self, decoded: Decoded) -> ParserResult {
+        match decoded {
+            Decoded::Bom => {
+                // The mark is at the beginning of the input, so nothing has been queued yet.
+                let fixes = check_bom(&self.options, self.decoder.encoding, self.end)?;
+                self.fixes.extend(fixes);
+                if self.options.keep_bom {
+                    self.write(BOM.as_bytes())?;
+                }
+                self.end.offset += BOM.len() as u64;
+                self.offset = self.end.offset;
+                self.text_start = self.end.offset;
+                Ok(())
+            }
+            Decoded::Bytes(bytes, len) => {
+                for &c in &bytes[..len] {
+                    self.options.check_size(self.end)?;
+                    self.end.advance(c);
+                    self.queue.push_back(c);
+                }
+                Ok(())
+            }
+            Decoded::Invalid => Err(SyntaxError::InvalidUtf16 { pos: self.end }.into()),
+        }
+    }
+
+    fn drain(&mut self) -> ParserResult {
+        while let Some(c) = self.queue.pop_front() {
+            self.scan(c)?;
+        }
+        Ok(())
+    }
+
+    fn scan(&mut self, c: u8) -> ParserResult {
+        if self.candidate.is_none() {
+            self.match_fence(c);
+        }
+        if self.candidate.is_none() && matches!(c, b'{' | b'[') && self.wants_candidate() {
+            let parser = Parser::new(self.options.clone(), Vec::new()).resume_at(Position::start());
+            self.candidate = Some((parser, self.offset));
+        }
+        self.offset += 1;
+        let Some((parser, _)) = &mut self.candidate else {
+            return Ok(());
+        };
+        self.candidate_input.push(c);
+        match parser.feed(&[c]) {
+            Ok(()) if parser.is_done() => self.accept(),
+            Ok(()) => Ok(()),
+            Err(RepairErr::Invalid(_)) => {
+                self.reject();
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Advances the match of [`JSON_FENCE`] over the byte `c` of the text.
+    fn match_fence(&mut self, c: u8) {
+        self.fence = match self.fence {
+            n if c == JSON_FENCE[n] => n + 1,
+            // A fence may have more than three backticks.
+            3 if c == b'`' => 3,
+            _ if c == b'`' => 1,
+            _ => 0,
+        };
+        if self.fence == JSON_FENCE.len() {
+            self.fenced = true;
+            self.fence = 0;
+        }
+    }
+
+    /// Whether a `{` or `[` in the text starts a candidate.
+    fn wants_candidate(&self) -> bool {
+        match self.mode {
+            Extract::All => true,
+            Extract::First => self.extracted.is_empty() && (self.held.is_none() || self.fenced),
+        }
+    }
+
+    /// Discards the current candidate. Its input up to the next `{` or `[` is regarded as text and
+    /// the rest is scanned again, unless the limit on the bytes scanned again is reached.
+    fn reject(&mut self) {
+        let (_, start) = self.candidate.take().expect("a candidate is being parsed");
+        let next = self.candidate_input[1..]
+            .iter()
+            .position(|c| matches!(c, b'{' | b'['))
+            .map(|i| i + 1);
+        if let Some(next) = next {
+            let len = (self.candidate_input.len() - next) as u64;
+            if self.rescanned + len <= self.end.offset.saturating_mul(MAX_RESCAN_FACTOR) {
+                self.rescanned += len;
+                self.offset = start + next as u64;
+                for &c in self.candidate_input[next..].iter().rev() {
+                    self.queue.push_front(c);
+                }
+            }
+        }
+        self.candidate_input.clear();
+    }
+
+    /// Writes the current candidate, which has been parsed successfully, or holds it back until a
+    /// value in a code block is found.
+    fn accept(&mut self) -> ParserResult {
+        let (mut parser, start) = self.candidate.take().expect("a candidate is being parsed");
+        parser.end()?;
+        let (value, fixes) = parser.into_parts();
+        self.candidate_input.clear();
+        let input = Span::new(start, self.offset);
+        if self.mode == Extract::First && !self.fenced {
+            self.held = Some((input, value, fixes));
+            return Ok(());
+        }
+        // A value held back before the code block is regarded as text.
+        self.held = None;
+        self.write_value(input, &value, fixes)
+    }
+
+    /// Writes an extracted value, which spans `input`, and its fixes.
+    fn write_value(&mut self, input: Span, value: &[u8], fixes: Vec<Fix>) -> ParserResult {
+        let start = input.start;
+        let at = self.output_offset;
+        if !self.extracted.is_empty() {
+            // The values are separated by line breaks.
+            self.write(b"\n")?;
+        }
+        self.remove_text(start, Span::new(at, self.output_offset));
+        for mut fix in fixes {
+            fix.input = Span::new(start + fix.input.start, start + fix.input.end);
+            fix.output = Span::new(
+                self.output_offset + fix.output.start,
+                self.output_offset + fix.output.end,
+            );
+            self.fixes.push(fix);
+        }
+        self.write(value)?;
+        self.extracted.push(input);
+        self.text_start = input.end;
+        Ok(())
+    }
+
+    /// Records the removal of the text before the input offset `end`, including whitespace.
+    fn remove_text(&mut self, end: u64, output: Span) {
+        if self.text_start < end {
+            self.fixes.push(Fix {
+                kind: FixKind::TextRemoved,
+                input: Span::new(self.text_start, end),
+                output,
+            });
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> ParserResult {
+        self.output.write_all(buf)?;
+        self.output_offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(W, RepairOk), RepairErr> {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        self.drain()?;
+        while let Some((parser, _)) = &mut self.candidate {
+            match parser.end() {
+                Ok(()) => self.accept()?,
+                Err(RepairErr::Invalid(_)) => {
+                    self.reject();
+                    self.drain()?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if let Some((input, value, fixes)) = self.held.take() {
+            self.write_value(input, &value, fixes)?;
+        }
+        if self.extracted.is_empty() {
+            return Err(SyntaxError::UnexpectedEof {
+                pos: self.end,
+                expected: Expected::Value,
+            }
+            .into());
+        }
+        self.remove_text(self.offset, Span::empty(self.output_offset));
+        if self.fixes.is_empty() && self.extracted.len() == 1 {
+            Ok((self.output, RepairOk::Valid))
+        } else {
+            let report = RepairReport {
+                fixes: self.fixes,
+                extracted: self.extracted,
+            };
+            Ok((self.output, RepairOk::Repaired(report)))
+        }
+    }
+}
+
+/// The limit on the bytes scanned again by [`RepairOptions::extract`], as a multiple of the input
+/// size.
+const MAX_RESCAN_FACTOR: u64 = 4;
+
+/// The opening of a Markdown code block whose value is preferred by [`Extract::First`].
+const JSON_FENCE: &[u8] = b"```json";
+
+/// The longest prefix recognized by [`Rule::Wrapper`], in bytes.
+const MAX_WRAPPER_PREFIX_LEN: usize = 256;
+
+/// A wrapper around the payload removed by [`Rule::Wrapper`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    /// `callback(...);`
+    Call,
+    /// `var x = ...;` or `export default ...;`
+    Assignment,
+}
+
+enum PrefixMatch {
+    Pending,
+    NoMatch,
+    /// The payload starts at the given offset.
+    Matched(Wrapper, usize),
+}
+
+/// Matches the beginning of the input against `callback(`, `var x =`, `x.y =` and
+/// `export default`. The match is decided when the first token of the payload is seen so that the
+/// whitespace before it belongs to the prefix.
+fn match_wrapper_prefix(buf: &[u8]) -> PrefixMatch {
+    // Split the input into identifiers, which may contain dots, and other bytes.
+    let mut tokens: Vec<(usize, &[u8])> = Vec::new();
+    let mut i = 0;
+    while i < buf.len() {
+        let start = i;
+        if is_ws(buf[i]) {
+            i += 1;
+            continue;
+        } else if is_word_start(buf[i]) {
+            while i < buf.len() && (is_word_char(buf[i]) || buf[i] == b'.') {
+                i += 1;
+            }
+            if i == buf.len() {
+                // The identifier may continue.
+                break;
+            }
+        } else {
+            i += 1;
+        }
+        tokens.push((start, &buf[start..i]));
+    }
+    let is_path = |token: &[u8]| is_word_start(token[0]);
+    let is_decl = |token: &[u8]| matches!(token, b"var" | b"let" | b"const");
+    match tokens[..] {
+        [(_, b"export"), (_, b"default"), (payload, _), ..] => {
+            PrefixMatch::Matched(Wrapper::Assignment, payload)
+        }
+        [(_, decl), (_, path), (_, b"="), (payload, _), ..] if is_decl(decl) && is_path(path) => {
+            PrefixMatch::Matched(Wrapper::Assignment, payload)
+        }
+        [(_, path), (_, b"="), (payload, _), ..] if is_path(path) && !is_decl(path) => {
+            PrefixMatch::Matched(Wrapper::Assignment, payload)
+        }
+        [(_, path), (_, b"("), (payload, _), ..] if is_path(path) => {
+            PrefixMatch::Matched(Wrapper::Call, payload)
+        }
+        [] | [(_, b"export"), (_, b"default")] => PrefixMatch::Pending,
+        [(_, path)] | [(_, path), (_, b"=" | b"(")] if is_path(path) => PrefixMatch::Pending,
+        [(_, decl), (_, path)] | [(_, decl), (_, path), (_, b"=")]
+            if is_decl(decl) && is_path(path) =>
+        {
+            PrefixMatch::Pending
+        }
+        _ => PrefixMatch::NoMatch,
+    }
+}
+
+/// Removes a JSONP callback or an assignment around the payload for [`Rule::Wrapper`] and passes
+/// the payload to the parser.
+///
+/// The input is decoded before the wrapper is matched, so that a byte order mark or UTF-16 input
+/// is handled as [`Parser`] does.
+struct Unwrapper<W: Write> {
+    parser: Parser<W>,
+    /// The beginning of the input held back until the wrapper is recognized.
+    head: Vec<u8>,
+    stage: UnwrapStage,
+    decoder: Decoder,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum UnwrapStage {
+    Prefix,
+    /// Passing the payload to the parser. The wrapper is `None` if the input has no wrapper.
+    Payload(Option<Wrapper>),
+    /// After the payload. `start` is the input offset where the suffix started, and `comment` is
+    /// the comment being skipped in the suffix.
+    Suffix {
+        wrapper: Wrapper,
+        start: u64,
+        paren: bool,
+        semicolon: bool,
+        comment: Option<Comment>,
+    },
+}
+
+impl<W: Write> Unwrapper<W> {
+    fn new(options: RepairOptions, output: W) -> Self {
+        Self {
+            parser: Parser::new(options, output).resume_at(Position::start()),
+            head: Vec::new(),
+            stage: UnwrapStage::Prefix,
+            decoder: Decoder::new(),
+        }
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for (i, &c) in buf.iter().enumerate() {
+            if let (UnwrapStage::Payload(None), Encoding::Utf8) =
+                (self.stage, self.decoder.encoding)
+            {
+                return self.parser.feed(&buf[i..]);
+            }
+            let decoded = self.decoder.decode(c);
+            self.feed_decoded(decoded)?;
+        }
+        Ok(())
+    }
+
+    fn feed_decoded(&mut self, decoded: Decoded) -> ParserResult {
+        match decoded {
+            // The mark precedes the wrapper, which has not been written yet.
+            Decoded::Bom => self.parser.bom(self.decoder.encoding),
+            Decoded::Bytes(bytes, len) => bytes[..len].iter().try_for_each(|&c| self.push(c)),
+            Decoded::Invalid => Err(SyntaxError::InvalidUtf16 {
+                pos: self.parser.pos,
+            }
+            .into()),
+        }
+    }
+
+    fn push(&mut self, c: u8) -> ParserResult {
+        match self.stage {
+            UnwrapStage::Prefix => {
+                self.head.push(c);
+                match match_wrapper_prefix(&self.head) {
+                    PrefixMatch::Pending if self.head.len() < MAX_WRAPPER_PREFIX_LEN => Ok(()),
+                    PrefixMatch::Matched(wrapper, start) => {
+                        let head = std::mem::take(&mut self.head);
+                        // The prefix follows the byte order mark, if any.
+                        let offset = self.parser.pos.offset;
+                        for &c in &head[..start] {
+                            self.parser.skip(c)?;
+                        }
+                        let at = self.parser.output.offset();
+                        self.parser.record(
+                            FixKind::WrapperRemoved,
+                            Span::new(offset, offset + start as u64),
+                            Span::empty(at),
+                        );
+                        self.stage = UnwrapStage::Payload(Some(wrapper));
+                        head[start..].iter().try_for_each(|&c| self.push(c))
+                    }
+                    _ => self.flush_head(),
+                }
+            }
+            UnwrapStage::Payload(None) => self.parser.feed(&[c]),
+            UnwrapStage::Payload(Some(wrapper)) => {
+                if !self.parser.is_done() || is_ws(c) {
+                    match self.parser.feed(&[c]) {
+                        // A number or a literal is terminated by the suffix.
+                        Err(RepairErr::Invalid(SyntaxError::TrailingData { .. })) => {}
+                        result => return result,
+                    }
+                }
+                self.stage = UnwrapStage::Suffix {
+                    wrapper,
+                    start: self.parser.pos.offset,
+                    paren: false,
+                    semicolon: false,
+                    comment: None,
+                };
+                self.push(c)
+            }
+            UnwrapStage::Suffix {
+                wrapper,
+                start,
+                paren,
+                semicolon,
+                comment,
+            } => {
+                // The comment is skipped as a part of the suffix.
+                let (skipped, comment) = match comment.map(|comment| comment.step(c)) {
+                    Some(CommentStep::Next(next)) => (true, Some(next)),
+                    Some(CommentStep::End) => (true, None),
+                    Some(CommentStep::EndBefore) => {
+                        self.stage = UnwrapStage::Suffix {
+                            wrapper,
+                            start,
+                            paren,
+                            semicolon,
+                            comment: None,
+                        };
+                        return self.push(c);
+                    }
+                    Some(CommentStep::Invalid) => {
+                        return Err(SyntaxError::InvalidValue {
+                            pos: self.parser.pos,
+                            expected: Expected::OneOf(b"/*"),
+                        }
+                        .into());
+                    }
+                    None if c == b'/' && self.parser.options.is_allowed(Rule::Comment) => {
+                        (true, Some(Comment::Slash))
+                    }
+                    None => (false, None),
+                };
+                let (paren, semicolon) = match c {
+                    _ if skipped || is_ws(c) => (paren, semicolon),
+                    b')' if wrapper == Wrapper::Call && !paren && !semicolon => (true, false),
+                    b';' if (wrapper != Wrapper::Call || paren) && !semicolon => (paren, true),
+                    _ => {
+                        return Err(SyntaxError::TrailingData {
+                            pos: self.parser.pos,
+                        }
+                        .into());
+                    }
+                };
+                self.stage = UnwrapStage::Suffix {
+                    wrapper,
+                    start,
+                    paren,
+                    semicolon,
+                    comment,
+                };
+                self.parser.skip(c)
+            }
+        }
+    }
+
+    /// Passes the held-back input to the parser as is.
+    fn flush_head(&mut self) -> ParserResult {
+        self.stage = UnwrapStage::Payload(None);
+        let head = std::mem::take(&mut self.head);
+        self.parser.feed(&head)
+    }
+
+    fn finish(mut self) -> Result<(W, RepairOk), RepairErr> {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        if let UnwrapStage::Prefix = self.stage {
+            self.flush_head()?;
+        }
+        self.parser.end()?;
+        if let UnwrapStage::Suffix {
+            comment: Some(comment),
+            ..
+        } = self.stage
+        {
+            comment.check_eof(&self.parser.options, self.parser.pos)?;
+        }
+        let paren = match self.stage {
+            UnwrapStage::Payload(wrapper) => wrapper != Some(Wrapper::Call),
+            UnwrapStage::Suffix { wrapper, paren, .. } => wrapper != Wrapper::Call || paren,
+            UnwrapStage::Prefix => unreachable!("the head has been flushed"),
+        };
+        if !paren && !self.parser.options.is_allowed(Rule::Truncation) {
+            return Err(SyntaxError::UnexpectedEof {
+                pos: self.parser.pos,
+                expected: Expected::Byte(b')'),
+            }
+            .into());
+        }
+        if let UnwrapStage::Suffix { start, .. } = self.stage {
+            let at = self.parser.output.offset();
+            self.parser.record(
+                FixKind::WrapperRemoved,
+                Span::new(start, self.parser.pos.offset),
+                Span::empty(at),
+            );
+        }
+        Ok(self.parser.into_result())
+    }
+}
+
+/// Splits the input into top-level values and repairs each of them for [`repair_stream`].
+///
+/// Each value is passed to `sink` with the whitespace and comments before it. A comment between
+/// values is a part of the separator, and a value parser is started only by a byte of a value.
+struct StreamRepairer<S: ValueSink> {
+    options: RepairOptions,
+    sink: S,
+    /// The position at the end of the input read so far.
+    pos: Position,
+    /// The parser of the current value and the input offset where it started.
+    value: Option<(Parser<Vec<u8>>, u64)>,
+    /// The whitespace and comments after the last value.
+    separator: Separator,
+    /// The comment being processed in the separator and the input offset where it started.
+    comment: Option<(Comment, u64)>,
+    decoder: Decoder,
+}
+
+impl<S: ValueSink> StreamRepairer<S> {
+    fn new(options: RepairOptions, sink: S) -> Self {
+        Self {
+            options,
+            sink,
+            pos: Position::start(),
+            value: None,
+            separator: Separator::new(0),
+            comment: None,
+            decoder: Decoder::new(),
+        }
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for &c in buf {
+            let decoded = self.decoder.decode(c);
+            self.feed_decoded(decoded)?;
+        }
+        Ok(())
+    }
+
+    fn feed_decoded(&mut self, decoded: Decoded) -> ParserResult {
+        match decoded {
+            Decoded::Bom => {
+                // The mark is handled here so that the sink can write it before anything else.
+                let fixes = check_bom(&self.options, self.decoder.encoding, self.pos)?;
+                self.pos.offset += BOM.len() as u64;
+                self.separator = Separator::new(self.pos.offset);
+                self.sink.bom(self.options.keep_bom, fixes)?;
+                Ok(())
+            }
+            Decoded::Bytes(bytes, len) => {
+                for &c in &bytes[..len] {
+                    self.options.check_size(self.pos)?;
+                    self.push(c)?;
+                }
+                Ok(())
+            }
+            Decoded::Invalid => Err(SyntaxError::InvalidUtf16 { pos: self.pos }.into()),
+        }
+    }
+
+    fn push(&mut self, c: u8) -> ParserResult {
+        if let Some((parser, _)) = &mut self.value {
+            if c == b'/' && self.options.is_allowed(Rule::Comment) && parser.in_top_level_scalar() {
+                // The comment terminates the number or the literal.
+                self.end_value(false)?;
+            } else {
+                let number = parser.in_top_level_number();
+                match parser.feed(&[c]) {
+                    Ok(()) if parser.is_done() => {
+                        // A number or a literal may be terminated by whitespace, which the parser
+                        // writes after the value.
+                        return self.end_value(is_ws(c));
+                    }
+                    Ok(()) => {
+                        self.pos.advance(c);
+                        return Ok(());
+                    }
+                    // A malformed number such as `1.2.3` is not split into values.
+                    Err(err @ RepairErr::Invalid(SyntaxError::TrailingData { .. }))
+                        if number && (c == b'.' || c.is_ascii_alphanumeric()) =>
+                    {
+                        return Err(err);
+                    }
+                    // A number or a literal is terminated by the next value.
+                    Err(RepairErr::Invalid(SyntaxError::TrailingData { .. })) => {
+                        self.end_value(false)?
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        if let Some((comment, start)) = self.comment {
+            return self.push_comment(c, comment, start);
+        }
+        if c == b'/' && self.options.is_allowed(Rule::Comment) {
+            self.comment = Some((Comment::Slash, self.pos.offset));
+            self.blank_comment_byte(c);
+            self.pos.advance(c);
+            return Ok(());
+        }
+        if is_ws(c) {
+            self.separator.output.push(c);
+            self.pos.advance(c);
+            return Ok(());
+        }
+        let parser = Parser::new(self.options.clone(), Vec::new()).resume_at(self.pos);
+        self.value = Some((parser, self.pos.offset));
+        self.push(c)
+    }
+
+    /// Processes a byte of a comment in the separator as [`Parser::step_comment`] does.
+    fn push_comment(&mut self, c: u8, comment: Comment, start: u64) -> ParserResult {
+        match comment.step(c) {
+            CommentStep::Next(next) => {
+                self.blank_comment_byte(c);
+                self.comment = Some((next, start));
+                self.pos.advance(c);
+                Ok(())
+            }
+            CommentStep::End => {
+                self.blank_comment_byte(c);
+                self.comment = None;
+                self.pos.advance(c);
+                self.end_comment(start, self.pos.offset);
+                Ok(())
+            }
+            CommentStep::EndBefore => {
+                self.comment = None;
+                self.end_comment(start, self.pos.offset);
+                self.push(c)
+            }
+            CommentStep::Invalid => Err(SyntaxError::InvalidValue {
+                pos: self.pos,
+                expected: Expected::OneOf(b"/*"),
+            }
+            .into()),
+        }
+    }
+
+    fn blank_comment_byte(&mut self, c: u8) {
+        self.separator
+            .output
+            .extend(self.options.comment_mode.blank(c));
+    }
+
+    fn end_comment(&mut self, start: u64, end: u64) {
+        let at = self.separator.output.len() as u64;
+        let fix = self.options.comment_mode.removed(start, end, at);
+        self.separator.fixes.push(fix);
+    }
+
+    /// Passes the current value to `f`. If `ws` is true, the last byte of the output is
+    /// whitespace after the value.
+    fn end_value(&mut self, ws: bool) -> ParserResult {
+        let (mut parser, start) = self.value.take().expect("a value is being parsed");
+        parser.end()?;
+        self.pos = parser.pos;
+        let (mut output, fixes) = parser.into_parts();
+        let mut end = self.pos.offset;
+        if ws {
+            end -= 1;
+        }
+        let mut separator = std::mem::replace(&mut self.separator, Separator::new(end));
+        separator.input.end = start;
+        if ws {
+            let c = output.pop().expect("the whitespace has been written");
+            self.separator.output.push(c);
+        }
+        let result = RepairOk::from_fixes(fixes);
+        self.sink.value(
+            separator,
+            StreamValue {
+                input: Span::new(start, end),
+                output: &output,
+                result,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Processes the end of input and returns the sink with the whitespace and comments after
+    /// the last value.
+    fn finish(mut self) -> Result<(S, Separator), RepairErr> {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        if self.value.is_some() {
+            self.end_value(false)?;
+        }
+        if let Some((comment, start)) = self.comment.take() {
+            comment.check_eof(&self.options, self.pos)?;
+            self.end_comment(start, self.pos.offset);
+        }
+        self.separator.input.end = self.pos.offset;
+        Ok((self.sink, self.separator))
+    }
+}
+
+/// The record separator that precedes each element of a JSON text sequence.
+const RS: u8 = 0x1E;
+
+/// Splits a JSON text sequence into elements at RS and repairs each of them for
+/// [`Stream::Sequence`].
+///
+/// Each element is passed to `sink` with the RS before it as the separator. The bytes before the
+/// first RS are regarded as an element as well, with no separator.
+struct SeqRepairer<S: ValueSink> {
+    options: RepairOptions,
+    sink: S,
+    /// The position at the end of the input read so far.
+    pos: Position,
+    parser: Parser<Vec<u8>>,
+    /// The input offset where the current element started.
+    start: u64,
+    /// The error in the current element, after which the element is not parsed.
+    error: Option<SyntaxError>,
+    /// Whether the current element consists of whitespace only. Such an element is ignored.
+    blank: bool,
+    /// Whether the last byte of the current element is whitespace.
+    ends_with_ws: bool,
+    /// Whether the current element is preceded by an RS.
+    separated: bool,
+    decoder: Decoder,
+}
+
+impl<S: ValueSink> SeqRepairer<S> {
+    fn new(options: RepairOptions, sink: S) -> Self {
+        let parser = Parser::new(options.clone(), Vec::new()).resume_at(Position::start());
+        Self {
+            options,
+            sink,
+            pos: Position::start(),
+            parser,
+            start: 0,
+            error: None,
+            blank: true,
+            ends_with_ws: false,
+            separated: false,
+            decoder: Decoder::new(),
+        }
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for &c in buf {
+            let decoded = self.decoder.decode(c);
+            self.feed_decoded(decoded)?;
+        }
+        Ok(())
+    }
+
+    fn feed_decoded(&mut self, decoded: Decoded) -> ParserResult {
+        match decoded {
+            Decoded::Bom => {
+                // The mark is handled here so that the sink can write it before the first RS.
+                let fixes = check_bom(&self.options, self.decoder.encoding, self.pos)?;
+                self.pos.offset += BOM.len() as u64;
+                self.start = self.pos.offset;
+                self.parser = Parser::new(self.options.clone(), Vec::new()).resume_at(self.pos);
+                self.sink.bom(self.options.keep_bom, fixes)?;
+                Ok(())
+            }
+            Decoded::Bytes(bytes, len) => {
+                for &c in &bytes[..len] {
+                    self.options.check_size(self.pos)?;
+                    self.push(c)?;
+                }
+                Ok(())
+            }
+            Decoded::Invalid => Err(SyntaxError::InvalidUtf16 { pos: self.pos }.into()),
+        }
+    }
+
+    fn push(&mut self, c: u8) -> ParserResult {
+        if c == RS {
+            self.end_element()?;
+            self.pos.advance(c);
+            self.start = self.pos.offset;
+            self.separated = true;
+            return Ok(());
+        }
+        self.pos.advance(c);
+        self.blank &= is_ws(c);
+        self.ends_with_ws = is_ws(c);
+        if self.error.is_none() {
+            match self.parser.feed(&[c]) {
+                Ok(()) => {}
+                Err(RepairErr::Invalid(err)) => self.error = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes the current element to `f` and prepares the parser for the next element.
+    fn end_element(&mut self) -> ParserResult {
+        let mut next_start = self.pos;
+        next_start.advance(RS);
+        let next = Parser::new(self.options.clone(), Vec::new()).resume_at(next_start);
+        let mut parser = std::mem::replace(&mut self.parser, next);
+        let error = self.error.take();
+        let blank = std::mem::replace(&mut self.blank, true);
+        if blank && !self.separated && self.start == self.pos.offset {
+            // The input is empty or ends with an element.
+            return Ok(());
+        }
+        // A top-level number not followed by whitespace may have lost its last digits.
+        let removed = if blank {
+            Some(FixKind::EmptyElementRemoved)
+        } else if error.is_none() && parser.in_top_level_number() && !self.ends_with_ws {
+            Some(FixKind::TruncatedElementRemoved)
+        } else if error.is_some() {
+            Some(FixKind::InvalidElementRemoved)
+        } else {
+            match parser.end() {
+                Ok(()) => None,
+                Err(RepairErr::Invalid(SyntaxError::UnexpectedEof { .. })) => {
+                    Some(FixKind::TruncatedElementRemoved)
+                }
+                Err(RepairErr::Invalid(_)) => Some(FixKind::InvalidElementRemoved),
+                Err(err) => return Err(err),
+            }
+        };
+        let input = Span::new(self.start, self.pos.offset);
+        let (output, fixes) = match removed {
+            // The RS before the element is removed as well.
+            Some(kind) => (
+                Vec::new(),
+                vec![Fix {
+                    kind,
+                    input: Span::new(self.start - u64::from(self.separated), input.end),
+                    output: Span::empty(0),
+                }],
+            ),
+            None => parser.into_parts(),
+        };
+        let result = RepairOk::from_fixes(fixes);
+        let separator = if self.separated {
+            Separator {
+                input: Span::new(self.start - 1, self.start),
+                output: vec![RS],
+                fixes: Vec::new(),
+            }
+        } else {
+            Separator::new(self.start)
+        };
+        self.sink.value(
+            separator,
+            StreamValue {
+                input,
+                output: &output,
+                result,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Processes the end of input and returns the sink with the empty separator at the end.
+    fn finish(mut self) -> Result<(S, Separator), RepairErr> {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        self.end_element()?;
+        Ok((self.sink, Separator::new(self.pos.offset)))
+    }
+}
+
+/// Repairs each line of the input for [`repair_lines`].
+struct LineRepairer<W: Write, F: FnMut(LineResult) -> std::io::Result<()>> {
+    options: RepairOptions,
+    output: W,
+    output_offset: u64,
+    /// The position at the end of the input read so far.
+    pos: Position,
+    /// The position where the current line started.
+    line_start: Position,
+    /// The current line as is, written when it cannot be repaired.
+    line: Vec<u8>,
+    parser: Parser<Vec<u8>>,
+    /// The error in the current line, after which the line is not parsed.
+    error: Option<SyntaxError>,
+    /// Receives the result of each line.
+    f: F,
+}
+
+impl<W: Write, F: FnMut(LineResult) -> std::io::Result<()>> LineRepairer<W, F> {
+    fn new(options: RepairOptions, output: W, f: F) -> Self {
+        let parser = Parser::new(options.clone(), Vec::new());
+        Self {
+            options,
+            output,
+            output_offset: 0,
+            pos: Position::start(),
+            line_start: Position::start(),
+            line: Vec::new(),
+            parser,
+            error: None,
+            f,
+        }
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for &c in buf {
+            self.options.check_size(self.pos)?;
+            // The lines are split before decoding, so a UTF-16 byte order mark is rejected.
+            if self.pos.offset == 1
+                && matches!((&self.line[..], c), ([0xFF], 0xFE) | ([0xFE], 0xFF))
+            {
+                return Err(SyntaxError::Utf16Unsupported {
+                    pos: Position::start(),
+                }
+                .into());
+            }
+            if c == b'\n' {
+                self.end_line(true)?;
+                self.pos.advance(c);
+                self.line_start = self.pos;
+                continue;
+            }
+            self.pos.advance(c);
+            self.line.push(c);
+            if self.error.is_none() {
+                match self.parser.feed(&[c]) {
+                    Ok(()) => {}
+                    Err(RepairErr::Invalid(err)) => self.error = Some(err),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the current line, followed by a line break if `eol` is true, and prepares the
+    /// parser for the next line.
+    fn end_line(&mut self, eol: bool) -> ParserResult {
+        let line = std::mem::take(&mut self.line);
+        let mut next_start = self.pos;
+        next_start.advance(b'\n');
+        let next = Parser::new(self.options.clone(), Vec::new()).resume_at(next_start);
+        let mut parser = std::mem::replace(&mut self.parser, next);
+        let error = self.error.take();
+        if line.iter().all(|&c| is_ws(c)) {
+            self.write(&line)?;
+        } else {
+            let result = match error {
+                Some(err) => Err(err),
+                None => {
+                    // A line of comments has no value, but it is not invalid either.
+                    let end = match parser.end_blank() {
+                        Ok(true) => Ok(()),
+                        Ok(false) => parser.end(),
+                        Err(err) => Err(err),
+                    };
+                    match end {
+                        Ok(()) => Ok(parser.into_parts()),
+                        Err(RepairErr::Invalid(err)) => Err(err),
+                        Err(err) => return Err(err),
+                    }
+                }
+            };
+            let result = match result {
+                Ok((value, fixes)) => {
+                    let at = self.output_offset;
+                    self.write(&value)?;
+                    let fixes = fixes
+                        .into_iter()
+                        .map(|mut fix| {
+                            fix.output = Span::new(at + fix.output.start, at + fix.output.end);
+                            fix
+                        })
+                        .collect();
+                    Ok(RepairOk::from_fixes(fixes))
+                }
+                Err(err) => match self.options.invalid_line {
+                    InvalidLine::Fail => return Err(err.into()),
+                    InvalidLine::Skip => return self.push_result(Err(err)),
+                    InvalidLine::PassThrough => {
+                        self.write(&line)?;
+                        Err(err)
+                    }
+                },
+            };
+            self.push_result(result)?;
+        }
+        if eol {
+            self.write(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn push_result(&mut self, result: Result<RepairOk, SyntaxError>) -> ParserResult {
+        (self.f)(LineResult {
+            line: self.line_start.line,
+            input: Span::new(self.line_start.offset, self.pos.offset),
+            result,
+        })?;
+        Ok(())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> ParserResult {
+        self.output.write_all(buf)?;
+        self.output_offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> ParserResult {
+        if !self.line.is_empty() {
+            self.end_line(false)?;
+        }
+        Ok(())
+    }
+}
+
+/// A push-style JSON parser.
+///
+/// The parser consumes the input one byte at a time and keeps the nesting of the containers in an
+/// explicit stack instead of the call stack, so that arbitrarily deep input can be handled.
+struct Parser<W: Write> {
+    options: RepairOptions,
+    decoder: Decoder,
+    output: Output<W>,
+    /// The position of the byte being processed.
+    pos: Position,
+    state: State,
+    stack: Vec<Container>,
+    /// Whitespace after a value or a comma. It is held back until the next token is seen because
+    /// a missing comma has to be inserted before it, or a trailing comma has to be removed.
+    ws: Vec<u8>,
+    /// Fixes inside `ws`. Their output spans are relative to the beginning of `ws`.
+    ws_fixes: Vec<Fix>,
+    /// The comment being processed and the input offset where it started.
+    comment: Option<(Comment, u64)>,
+    /// The length of the string or number being processed, used to enforce the limits.
+    token_len: u64,
+    /// The position where the number or the literal being processed started.
+    token_start: Position,
+    /// The incomplete UTF-8 sequence in the string or the unquoted key being processed.
+    utf8: Vec<u8>,
+    /// The hexadecimal digits and the input offset of a high surrogate escape, held back until
+    /// it is known whether a low surrogate escape follows.
+    high_surrogate: Option<([u8; 4], u64)>,
+    /// The literal being processed in [`State::Word`].
+    word: Vec<u8>,
+    max_literal_len: usize,
+    /// The input offset where the last string value ended.
+    string_end: u64,
+    /// The quote that started the string being processed.
+    quote: u8,
+    /// The input and output offsets where the string being processed started.
+    string_start: (u64, u64),
+    /// The input offset and the number of fixes when the output started being held for
+    /// [`Rule::Truncation`].
+    item_start: (u64, usize),
+    fixes: Vec<Fix>,
+}
+
+type ParserResult = Result<(), RepairErr>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Object,
+    Array,
+}
+
+impl Container {
+    fn closer(self) -> u8 {
+        match self {
+            Self::Object => b'}',
+            Self::Array => b']',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Expecting a value, e.g., at the beginning of the input or after a colon.
+    Value,
+    /// In the middle of `null`, `true` or `false`. The slice holds the remaining bytes.
+    Literal(&'static [u8]),
+    /// In a literal that may be replaced by [`Rule::Literal`]. The bytes are held in `word`.
+    Word,
+    String {
+        key: bool,
+    },
+    Escape {
+        key: bool,
+    },
+    /// In a `\uXXXX` escape. The hexadecimal digits are held back until all of them are read.
+    Unicode {
+        key: bool,
+        hex: [u8; 4],
+        len: u8,
+    },
+    /// In a `\xHH` escape, which is converted by [`Rule::Escape`].
+    Hex {
+        key: bool,
+        hex: [u8; 2],
+        len: u8,
+    },
+    /// After the minus sign of a number.
+    Minus,
+    /// After the plus sign of a number, which is removed by [`Rule::PlusSign`].
+    Plus,
+    /// After the integer part `0`. The zero at the input offset `start` is written with the next
+    /// byte because it may be a leading zero or the prefix of `0x`, `0o` or `0b`.
+    Zero {
+        start: u64,
+    },
+    IntegerDigits,
+    /// After a decimal point with no integer part before it. A zero has been inserted for the
+    /// integer part, and a digit must follow.
+    LeadingPoint,
+    /// After the decimal point.
+    Fraction,
+    FractionDigits,
+    /// After `e` or `E`.
+    Exponent,
+    /// After the sign of an exponent.
+    ExponentSign,
+    ExponentDigits,
+    /// In a hexadecimal, octal or binary integer that started at the input offset `start`.
+    /// `value` is `None` until the first digit.
+    Radix {
+        start: u64,
+        radix: u32,
+        value: Option<u128>,
+    },
+    /// Just after `{` or `[`.
+    Open,
+    /// After a comma in an empty container, e.g., `[ ,`.
+    OpenComma,
+    /// Expecting an object key.
+    Key,
+    /// In an unquoted object key.
+    BareKey,
+    /// After an object key, expecting a colon. `key_end` is the input offset where the key ended.
+    AfterKey {
+        key_end: u64,
+    },
+    /// After a value in a container. `value_end` is the input offset where the value ended.
+    AfterValue {
+        value_end: u64,
+    },
+    /// After a comma in a container. `comma` is the input offset of the comma.
+    AfterComma {
+        comma: u64,
+    },
+    /// After the top-level value.
+    End,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comment {
+    /// After the first `/`.
+    Slash,
+    Line,
+    Block,
+    /// After a `*` in a block comment.
+    BlockStar,
+}
+
+/// What [`Comment::step`] did with a byte.
+enum CommentStep {
+    /// The byte is a part of the comment, which continues in the given state.
+    Next(Comment),
+    /// The byte is the last one of the comment.
+    End,
+    /// The comment ended before the byte, which is not a part of it.
+    EndBefore,
+    /// The byte cannot follow the first `/`.
+    Invalid,
+}
+
+impl Comment {
+    /// Advances the comment over the byte `c`.
+    fn step(self, c: u8) -> CommentStep {
+        match (self, c) {
+            (Self::Slash, b'/') => CommentStep::Next(Self::Line),
+            (Self::Slash, b'*') => CommentStep::Next(Self::Block),
+            (Self::Slash, _) => CommentStep::Invalid,
+            // The line break is not a part of the comment.
+            (Self::Line, b'\n' | b'\r') => CommentStep::EndBefore,
+            (Self::Line, _) => CommentStep::Next(Self::Line),
+            (Self::Block | Self::BlockStar, b'*') => CommentStep::Next(Self::BlockStar),
+            (Self::BlockStar, b'/') => CommentStep::End,
+            (Self::Block | Self::BlockStar, _) => CommentStep::Next(Self::Block),
+        }
+    }
+
+    /// Checks whether the comment may be terminated by the end of input at `pos`. A line comment
+    /// may be, and so may any comment if [`Rule::Truncation`] is allowed.
+    fn check_eof(self, options: &RepairOptions, pos: Position) -> Result<(), SyntaxError> {
+        match self {
+            Self::Line => Ok(()),
+            _ if options.is_allowed(Rule::Truncation) => Ok(()),
+            Self::Slash => Err(SyntaxError::UnexpectedEof {
+                pos,
+                expected: Expected::OneOf(b"/*"),
+            }),
+            Self::Block | Self::BlockStar => Err(SyntaxError::UnexpectedEof {
+                pos,
+                expected: Expected::CommentEnd,
+            }),
+        }
+    }
+}
+
+/// Whether [`Parser::step`] consumed the given byte or it has to be processed again in the new
+/// state.
+enum Step {
+    Consumed,
+    Reprocess,
+}
+
+type StepResult = Result<Step, RepairErr>;
+
+fn is_ws(c: u8) -> bool {
+    matches!(c, 0x09 | 0x0A | 0x0D | 0x20)
+}
+
+fn is_word_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'$' || c == b'_'
+}
+
+fn is_word_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'$' || c == b'_'
+}
+
+/// Returns whether the byte can be a part of an unquoted object key. Every byte of a non-ASCII
+/// character is accepted, and the character is checked by `Parser::check_key_char`.
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'$' || c == b'_' || c >= 0x80
+}
+
+/// Returns the length of the UTF-8 sequence starting with `lead`, or `None` if no sequence can
+/// start with it.
+fn utf8_len(lead: u8) -> Option<usize> {
+    match lead {
+        0x00..=0x7F => Some(1),
+        0xC2..=0xDF => Some(2),
+        0xE0..=0xEF => Some(3),
+        0xF0..=0xF4 => Some(4),
+        _ => None,
+    }
+}
+
+/// Returns whether `c` continues the incomplete UTF-8 sequence `seq`. Overlong encodings,
+/// surrogates and code points above U+10FFFF are rejected.
+fn utf8_continues(seq: &[u8], c: u8) -> bool {
+    let range = match (seq[0], seq.len()) {
+        (0xE0, 1) => 0xA0..=0xBF,
+        (0xED, 1) => 0x80..=0x9F,
+        (0xF0, 1) => 0x90..=0xBF,
+        (0xF4, 1) => 0x80..=0x8F,
+        _ => 0x80..=0xBF,
+    };
+    range.contains(&c)
+}
+
+/// The byte order mark in UTF-8.
+const BOM: &str = "\u{FEFF}";
+
+/// Checks the byte order mark at `pos`, the beginning of the input in `encoding`, and returns the
+/// fixes for it. The mark is written as is if [`RepairOptions::keep_bom`] is set.
+fn check_bom(
+    options: &RepairOptions,
+    encoding: Encoding,
+    pos: Position,
+) -> Result<Vec<Fix>, SyntaxError> {
+    if !options.keep_bom && !options.is_allowed(Rule::Bom) {
+        return Err(SyntaxError::ByteOrderMark { pos });
+    }
+    let input = Span::new(0, BOM.len() as u64);
+    let output = if options.keep_bom {
+        input
+    } else {
+        Span::empty(0)
+    };
+    let mut fixes = Vec::new();
+    if encoding != Encoding::Utf8 {
+        fixes.push(Fix {
+            kind: FixKind::Utf16Transcoded,
+            input,
+            output,
+        });
+    }
+    if !options.keep_bom {
+        fixes.push(Fix {
+            kind: FixKind::BomRemoved,
+            input,
+            output,
+        });
+    }
+    Ok(fixes)
+}
+
+/// Detects the encoding of the input by its byte order mark and transcodes UTF-16 to UTF-8.
+struct Decoder {
+    encoding: Encoding,
+    /// The bytes at the beginning of the input that may be a part of a byte order mark.
+    head: Vec<u8>,
+    /// The first byte of a UTF-16 code unit.
+    byte: Option<u8>,
+    /// A UTF-16 high surrogate waiting for a low surrogate.
+    high: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// The beginning of the input has not been seen yet.
+    Unknown,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+enum Decoded {
+    /// A byte order mark at the beginning of the input.
+    Bom,
+    /// UTF-8 bytes, which may be empty.
+    Bytes([u8; 4], usize),
+    /// An unpaired UTF-16 surrogate or an incomplete code unit.
+    Invalid,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Self {
+            encoding: Encoding::Unknown,
+            head: Vec::new(),
+            byte: None,
+            high: None,
+        }
+    }
+
+    fn decode(&mut self, c: u8) -> Decoded {
+        match self.encoding {
+            Encoding::Utf8 => Decoded::Bytes([c, 0, 0, 0], 1),
+            Encoding::Unknown => {
+                self.head.push(c);
+                self.encoding = match self.head[..] {
+                    [0xEF, 0xBB, 0xBF] => Encoding::Utf8,
+                    [0xFF, 0xFE] => Encoding::Utf16Le,
+                    [0xFE, 0xFF] => Encoding::Utf16Be,
+                    [0xEF] | [0xEF, 0xBB] | [0xFF] | [0xFE] => {
+                        return Decoded::Bytes([0; 4], 0);
+                    }
+                    _ => return self.flush_head(),
+                };
+                self.head.clear();
+                Decoded::Bom
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let Some(first) = self.byte.take() else {
+                    self.byte = Some(c);
+                    return Decoded::Bytes([0; 4], 0);
+                };
+                let unit = if self.encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes([first, c])
+                } else {
+                    u16::from_be_bytes([first, c])
+                };
+                let code = match (self.high.take(), unit) {
+                    (None, 0xD800..=0xDBFF) => {
+                        self.high = Some(unit);
+                        return Decoded::Bytes([0; 4], 0);
+                    }
+                    (Some(high), 0xDC00..=0xDFFF) => {
+                        0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(unit) - 0xDC00)
+                    }
+                    (Some(_), _) | (None, 0xDC00..=0xDFFF) => return Decoded::Invalid,
+                    (None, unit) => u32::from(unit),
+                };
+                let mut bytes = [0; 4];
+                let len = char::from_u32(code)
+                    .expect("surrogates are paired")
+                    .encode_utf8(&mut bytes)
+                    .len();
+                Decoded::Bytes(bytes, len)
+            }
+        }
+    }
+
+    /// Returns the bytes held back at the end of the input.
+    fn finish(&mut self) -> Decoded {
+        match self.encoding {
+            Encoding::Unknown => self.flush_head(),
+            _ if self.byte.is_some() || self.high.is_some() => Decoded::Invalid,
+            _ => Decoded::Bytes([0; 4], 0),
+        }
+    }
+
+    /// Passes through the bytes that turned out not to be a byte order mark.
+    fn flush_head(&mut self) -> Decoded {
+        self.encoding = Encoding::Utf8;
+        let mut bytes = [0; 4];
+        let len = self.head.len();
+        bytes[..len].copy_from_slice(&self.head);
+        self.head.clear();
+        Decoded::Bytes(bytes, len)
+    }
+}
+
+struct Output<W: Write> {
+    inner: W,
+    offset: u64,
+    holding: bool,
+    /// The output written since [`Output::hold`] was called.
+    held: Vec<u8>,
+}
+
+impl<W: Write> Output<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            holding: false,
+            held: Vec::new(),
+        }
+    }
+
+    /// Returns the number of bytes written so far, including the held ones.
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn is_holding(&self) -> bool {
+        self.holding
+    }
+
+    /// Returns the underlying writer, which does not have the held output yet.
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Starts holding back the output so that it can be discarded by [`Output::rollback`].
+    fn hold(&mut self) -> std::io::Result<()> {
+        self.commit()?;
+        self.holding = true;
+        Ok(())
+    }
+
+    /// Writes the held output.
+    fn commit(&mut self) -> std::io::Result<()> {
+        if self.holding {
+            self.inner.write_all(&self.held)?;
+            self.held.clear();
+            self.holding = false;
+        }
+        Ok(())
+    }
+
+    /// Discards the held output.
+    fn rollback(&mut self) {
+        self.take_held();
+    }
+
+    /// Discards the held output and returns it.
+    fn take_held(&mut self) -> Vec<u8> {
+        let held = std::mem::take(&mut self.held);
+        self.offset -= held.len() as u64;
+        self.holding = false;
+        held
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = if self.holding {
+            self.held.extend_from_slice(buf);
+            buf.len()
+        } else {
+            self.inner.write(buf)?
+        };
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Parser<W> {
+    fn new(options: RepairOptions, output: W) -> Self {
+        let max_literal_len = options.max_literal_len();
+        Self {
+            options,
+            decoder: Decoder::new(),
+            output: Output::new(output),
+            pos: Position::start(),
+            state: State::Value,
+            stack: Vec::new(),
+            ws: Vec::new(),
+            ws_fixes: Vec::new(),
+            comment: None,
+            token_len: 0,
+            token_start: Position::start(),
+            utf8: Vec::new(),
+            high_surrogate: None,
+            word: Vec::new(),
+            max_literal_len,
+            string_end: 0,
+            quote: b'"',
+            string_start: (0, 0),
+            item_start: (0, 0),
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Starts parsing in the middle of the input at `pos`, where no byte order mark is expected.
+    fn resume_at(mut self, pos: Position) -> Self {
+        self.pos = pos;
+        self.token_start = pos;
+        self.decoder.encoding = Encoding::Utf8;
+        self
+    }
+
+    /// Returns whether a top-level number or a literal for [`Rule::Literal`] is being parsed,
+    /// which is terminated by any byte that cannot continue it.
+    fn in_top_level_scalar(&self) -> bool {
+        self.in_top_level_number() || (self.stack.is_empty() && matches!(self.state, State::Word))
+    }
+
+    /// Returns whether a top-level number is being parsed.
+    fn in_top_level_number(&self) -> bool {
+        self.stack.is_empty()
+            && matches!(
+                self.state,
+                State::Minus
+                    | State::Plus
+                    | State::Zero { .. }
+                    | State::IntegerDigits
+                    | State::LeadingPoint
+                    | State::Fraction
+                    | State::FractionDigits
+                    | State::Exponent
+                    | State::ExponentSign
+                    | State::ExponentDigits
+                    | State::Radix { .. }
+            )
+    }
+
+    fn feed(&mut self, buf: &[u8]) -> ParserResult {
+        for &c in buf {
+            let decoded = self.decoder.decode(c);
+            self.feed_decoded(decoded)?;
+        }
+        Ok(())
+    }
+
+    fn feed_decoded(&mut self, decoded: Decoded) -> ParserResult {
+        match decoded {
+            Decoded::Bom => self.bom(self.decoder.encoding),
+            Decoded::Bytes(bytes, len) => {
+                for &c in &bytes[..len] {
+                    self.feed_byte(c)?;
+                }
+                Ok(())
+            }
+            Decoded::Invalid => Err(SyntaxError::InvalidUtf16 { pos: self.pos }.into()),
+        }
+    }
+
+    fn feed_byte(&mut self, c: u8) -> ParserResult {
+        self.options.check_size(self.pos)?;
+        while let Step::Reprocess = self.step(c)? {}
+        self.pos.advance(c);
+        Ok(())
+    }
+
+    /// Advances the position over a byte that is not a part of the JSON.
+    fn skip(&mut self, c: u8) -> ParserResult {
+        self.options.check_size(self.pos)?;
+        self.pos.advance(c);
+        Ok(())
+    }
+
+    /// Keeps, removes or reports the byte order mark at the beginning of the input in `encoding`.
+    /// The column of the position does not count it.
+    fn bom(&mut self, encoding: Encoding) -> ParserResult {
+        let fixes = check_bom(&self.options, encoding, self.pos)?;
+        self.fixes.extend(fixes);
+        if self.options.keep_bom {
+            self.output.write_all(BOM.as_bytes())?;
+        }
+        self.pos.offset += BOM.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(W, RepairOk), RepairErr> {
+        self.end()?;
+        Ok(self.into_result())
+    }
+
+    /// Processes the end of input.
+    fn end(&mut self) -> ParserResult {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        self.end_open_comment()?;
+        if self.number_can_end() {
+            self.end_number()?;
+        }
+        if let State::Word = self.state {
+            // An unknown literal is removed by Rule::Truncation.
+            let known = !matches!(
+                self.options.find_literal(&self.word),
+                LiteralLookup::Unknown
+            ) || matches!(&self.word[..], b"null" | b"true" | b"false");
+            if known || !self.options.is_allowed(Rule::Truncation) {
+                self.end_word()?;
+            }
+        }
+        if !matches!(self.state, State::End) && self.options.is_allowed(Rule::Truncation) {
+            self.complete_truncated()?;
+        }
+        if !matches!(self.state, State::End) {
+            return Err(SyntaxError::UnexpectedEof {
+                pos: self.pos,
+                expected: self.expected(),
+            }
+            .into());
+        }
+        self.output.commit()?;
+        Ok(())
+    }
+
+    /// Processes the end of input if only whitespace and comments have been read, and returns
+    /// whether that is the case. Otherwise, [`Parser::end`] has to be called.
+    fn end_blank(&mut self) -> Result<bool, RepairErr> {
+        let decoded = self.decoder.finish();
+        self.feed_decoded(decoded)?;
+        if !self.stack.is_empty() || !matches!(self.state, State::Value) {
+            return Ok(false);
+        }
+        self.end_open_comment()?;
+        self.output.commit()?;
+        Ok(true)
+    }
+
+    /// Ends the comment terminated by the end of input, if any.
+    fn end_open_comment(&mut self) -> ParserResult {
+        if let Some((comment, start)) = self.comment.take() {
+            comment.check_eof(&self.options, self.pos)?;
+            self.end_comment(start, self.pos.offset);
+        }
+        Ok(())
+    }
+
+    /// Returns whether the top-level value has been parsed. The rest of the input is not needed
+    /// unless it is checked for trailing data.
+    fn is_done(&self) -> bool {
+        matches!(self.state, State::End) && self.comment.is_none()
+    }
+
+    fn into_parts(self) -> (W, Vec<Fix>) {
+        (self.output.inner, self.fixes)
+    }
+
+    /// Completes the input that ends in the current state.
+    fn complete_truncated(&mut self) -> ParserResult {
+        let eof = self.pos.offset;
+        match self.state {
+            State::String { key: false }
+            | State::Escape { key: false }
+            | State::Unicode { key: false, .. }
+            | State::Hex { key: false, .. } => {
+                self.end_high_surrogate()?;
+                // Drop an incomplete escape sequence, or escape its backslash, and close the
+                // string.
+                let start = match self.state {
+                    State::String { .. } => {
+                        // Drop an incomplete UTF-8 sequence as well.
+                        let start = eof - self.utf8.len() as u64;
+                        self.utf8.clear();
+                        start
+                    }
+                    _ if self.options.is_allowed(Rule::Escape) => {
+                        self.escape_backslash()?;
+                        eof
+                    }
+                    State::Escape { .. } => eof - 1,
+                    State::Unicode { len, .. } | State::Hex { len, .. } => eof - 2 - u64::from(len),
+                    _ => unreachable!(),
+                };
+                let at = self.output.offset();
+                self.output.write_all(b"\"")?;
+                self.record_single_quotes(eof);
+                self.record(
+                    FixKind::UnterminatedStringClosed,
+                    Span::new(start, eof),
+                    Span::new(at, at + 1),
+                );
+                self.end_value(eof);
+            }
+            State::Open | State::OpenComma | State::End => {}
+            State::AfterValue { .. } => self.flush_ws()?,
+            State::AfterComma { comma } => {
+                self.remove_trailing_comma(comma);
+                self.flush_ws()?;
+            }
+            _ => {
+                // A dangling key, or an incomplete literal or number. Discard the whole member or
+                // element, including the comma before it.
+                if !self.output.is_holding() {
+                    // The top-level value cannot be discarded.
+                    return Ok(());
+                }
+                let (start, fixes) = self.item_start;
+                self.output.rollback();
+                self.fixes.truncate(fixes);
+                let at = self.output.offset();
+                self.record(
+                    FixKind::IncompleteValueRemoved,
+                    Span::new(start, eof),
+                    Span::empty(at),
+                );
+            }
+        }
+        while let Some(container) = self.stack.pop() {
+            let at = self.output.offset();
+            self.output.write_all(&[container.closer()])?;
+            self.record(
+                FixKind::UnclosedContainerClosed,
+                Span::empty(eof),
+                Span::new(at, at + 1),
+            );
+        }
+        self.state = State::End;
+        Ok(())
+    }
+
+    /// Records the conversion of the string that has just been closed if it was single-quoted.
+    fn record_single_quotes(&mut self, end: u64) {
+        if self.quote == b'\'' {
+            let (input_start, output_start) = self.string_start;
+            let output_end = self.output.offset();
+            self.record(
+                FixKind::SingleQuotesReplaced,
+                Span::new(input_start, end),
+                Span::new(output_start, output_end),
+            );
+        }
+    }
+
+    /// Marks the start of a member or an element, which may be discarded later if the input ends
+    /// in the middle of it. `start` is the input offset of the comma before it, if any.
+    fn start_item(&mut self, start: u64) -> ParserResult {
+        if (self.options.is_allowed(Rule::Truncation)
+            || self.may_be_misplaced_key()
+            || self.may_drop_lone_key())
+            && !self.stack.is_empty()
+        {
+            self.output.hold()?;
+            self.item_start = (start, self.fixes.len());
+        }
+        Ok(())
+    }
+
+    /// Returns whether an element of the current array may turn out to be a key of the parent
+    /// object, e.g., `"b"` in `{"a": [1, "b": 2}`. Such an element is held back until the next
+    /// token is seen.
+    fn may_be_misplaced_key(&self) -> bool {
+        self.options.is_allowed(Rule::MismatchedBracket)
+            && matches!(self.stack[..], [.., Container::Object, Container::Array])
+    }
+
+    /// Returns whether a key in the current object may be removed by [`Rule::LoneKey`].
+    fn may_drop_lone_key(&self) -> bool {
+        self.options.is_allowed(Rule::LoneKey)
+            && self.options.lone_key == LoneKey::Drop
+            && self.stack.last() == Some(&Container::Object)
+    }
+
+    fn into_result(self) -> (W, RepairOk) {
+        let (output, fixes) = self.into_parts();
+        (output, RepairOk::from_fixes(fixes))
+    }
+
+    fn record(&mut self, kind: FixKind, input: Span, output: Span) {
+        self.fixes.push(Fix {
+            kind,
+            input,
+            output,
+        });
+    }
+
+    fn remove_trailing_comma(&mut self, comma: u64) {
+        let at = self.output.offset();
+        self.record(
+            FixKind::TrailingCommaRemoved,
+            Span::new(comma, comma + 1),
+            Span::empty(at),
+        );
+    }
+
+    fn insert_missing_comma(&mut self, at: u64) -> ParserResult {
+        let start = self.output.offset();
+        self.output.write_all(b",")?;
+        self.record(
+            FixKind::MissingCommaInserted,
+            Span::empty(at),
+            Span::new(start, start + 1),
+        );
+        Ok(())
+    }
+
+    fn container(&self) -> Container {
+        *self
+            .stack
+            .last()
+            .expect("the state is only reachable inside a container")
+    }
+
+    /// Returns what the current state accepts.
+    fn expected(&self) -> Expected {
+        match self.state {
+            State::Value => Expected::Value,
+            State::Literal(rest) => Expected::Byte(rest[0]),
+            State::Word => Expected::Value,
+            State::String { .. } => Expected::Byte(self.quote),
+            State::Escape { .. } => Expected::Escape,
+            State::Unicode { .. } | State::Hex { .. } => Expected::HexDigit,
+            State::Radix { radix: 16, .. } => Expected::HexDigit,
+            State::Minus
+            | State::Plus
+            | State::LeadingPoint
+            | State::Fraction
+            | State::Exponent
+            | State::ExponentSign
+            | State::Radix { .. } => Expected::Digit,
+            State::Open => match self.container() {
+                Container::Object => Expected::OneOf(b"\"}"),
+                Container::Array => Expected::Value,
+            },
+            State::OpenComma => Expected::Byte(self.container().closer()),
+            State::Key => Expected::Key,
+            State::BareKey | State::AfterKey { .. } => Expected::Byte(b':'),
+            State::AfterValue { .. } => match self.container() {
+                Container::Object => Expected::OneOf(b",}"),
+                Container::Array => Expected::OneOf(b",]"),
+            },
+            State::AfterComma { .. } => match self.container() {
+                Container::Object => Expected::Key,
+                Container::Array => Expected::Value,
+            },
+            State::Zero { .. }
+            | State::IntegerDigits
+            | State::FractionDigits
+            | State::ExponentDigits
+            | State::End => unreachable!("the state accepts the end of input"),
+        }
+    }
+
+    fn invalid(&self) -> RepairErr {
+        SyntaxError::InvalidValue {
+            pos: self.pos,
+            expected: self.expected(),
+        }
+        .into()
+    }
+
+    /// Moves to the state after a value. `value_end` is the input offset where the value ended.
+    fn end_value(&mut self, value_end: u64) {
+        self.state = if self.stack.is_empty() {
+            State::End
+        } else {
+            State::AfterValue { value_end }
+        };
+    }
+
+    /// Moves to the state that expects the next member or element of the current container.
+    fn next_item(&mut self) {
+        self.state = match self.container() {
+            Container::Object => State::Key,
+            Container::Array => State::Value,
+        };
+    }
+
+    fn push_container(&mut self, container: Container) -> ParserResult {
+        if let Some(limit) = self.options.max_depth
+            && self.stack.len() >= limit
+        {
+            return Err(SyntaxError::TooDeep {
+                pos: self.pos,
+                limit,
+            }
+            .into());
+        }
+        // Once a container is opened, it is closed rather than discarded.
+        self.output.commit()?;
+        self.stack.push(container);
+        self.state = State::Open;
+        Ok(())
+    }
+
+    fn count_string_byte(&mut self) -> ParserResult {
+        self.token_len += 1;
+        if let Some(limit) = self.options.max_string_length
+            && self.token_len > limit
+        {
+            return Err(SyntaxError::StringTooLong {
+                pos: self.pos,
+                limit,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn count_number_byte(&mut self) -> ParserResult {
+        self.token_len += 1;
+        if let Some(limit) = self.options.max_number_length
+            && self.token_len > limit
+        {
+            return Err(SyntaxError::NumberTooLong {
+                pos: self.pos,
+                limit,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn flush_ws(&mut self) -> ParserResult {
+        let base = self.output.offset();
+        self.output.write_all(&self.ws)?;
+        self.ws.clear();
+        for mut fix in self.ws_fixes.drain(..) {
+            fix.output = Span::new(base + fix.output.start, base + fix.output.end);
+            self.fixes.push(fix);
+        }
+        Ok(())
+    }
+
+    /// Returns whether whitespace is held back in `ws` in the current state.
+    fn holds_ws(&self) -> bool {
+        matches!(
+            self.state,
+            State::AfterKey { .. } | State::AfterValue { .. } | State::AfterComma { .. }
+        )
+    }
+
+    /// Returns whether whitespace, and thus a comment, may appear in the current state.
+    fn accepts_ws(&self) -> bool {
+        matches!(
+            self.state,
+            State::Value
+                | State::Open
+                | State::OpenComma
+                | State::Key
+                | State::AfterKey { .. }
+                | State::AfterValue { .. }
+                | State::AfterComma { .. }
+                | State::End
+        )
+    }
+
+    fn write_ws(&mut self, c: u8) -> ParserResult {
+        if self.holds_ws() {
+            self.ws.push(c);
+            Ok(())
+        } else {
+            Ok(self.output.write_all(&[c])?)
+        }
+    }
+
+    fn step_comment(&mut self, c: u8, comment: Comment, start: u64) -> StepResult {
+        match comment.step(c) {
+            CommentStep::Next(next) => {
+                self.blank_comment_byte(c)?;
+                self.comment = Some((next, start));
+                Ok(Step::Consumed)
+            }
+            CommentStep::End => {
+                self.blank_comment_byte(c)?;
+                self.comment = None;
+                self.end_comment(start, self.pos.offset + 1);
+                Ok(Step::Consumed)
+            }
+            CommentStep::EndBefore => {
+                self.comment = None;
+                self.end_comment(start, self.pos.offset);
+                Ok(Step::Reprocess)
+            }
+            CommentStep::Invalid => Err(SyntaxError::InvalidValue {
+                pos: self.pos,
+                expected: Expected::OneOf(b"/*"),
+            }
+            .into()),
+        }
+    }
+
+    fn blank_comment_byte(&mut self, c: u8) -> ParserResult {
+        match self.options.comment_mode.blank(c) {
+            Some(c) => self.write_ws(c),
+            None => Ok(()),
+        }
+    }
+
+    fn end_comment(&mut self, start: u64, end: u64) {
+        let mode = self.options.comment_mode;
+        if self.holds_ws() {
+            let fix = mode.removed(start, end, self.ws.len() as u64);
+            self.ws_fixes.push(fix);
+        } else {
+            let fix = mode.removed(start, end, self.output.offset());
+            self.fixes.push(fix);
+        }
+    }
+
+    fn step(&mut self, c: u8) -> StepResult {
+        if let Some((comment, start)) = self.comment {
+            return self.step_comment(c, comment, start);
+        }
+        if c == b'/' && self.accepts_ws() && self.options.is_allowed(Rule::Comment) {
+            self.comment = Some((Comment::Slash, self.pos.offset));
+            self.blank_comment_byte(c)?;
+            return Ok(Step::Consumed);
+        }
+        match self.state {
+            State::Value => {
+                if is_ws(c) {
+                    self.output.write_all(&[c])?;
+                    Ok(Step::Consumed)
+                } else {
+                    self.step_value(c)
+                }
+            }
+            State::Literal(rest) => {
+                if c != rest[0] {
+                    return Err(self.invalid());
+                }
+                self.output.write_all(&[c])?;
+                if rest.len() == 1 {
+                    self.end_value(self.pos.offset + 1);
+                } else {
+                    self.state = State::Literal(&rest[1..]);
+                }
+                Ok(Step::Consumed)
+            }
+            State::Word => {
+                if !is_word_char(c) {
+                    self.end_word()?;
+                    return Ok(Step::Reprocess);
+                }
+                if self.word.len() >= self.max_literal_len {
+                    return Err(self.invalid_word());
+                }
+                self.word.push(c);
+                Ok(Step::Consumed)
+            }
+            State::String { key } => self.step_string(c, key),
+            State::Escape { key } => self.step_escape(c, key),
+            State::Unicode { key, mut hex, len } => {
+                if !c.is_ascii_hexdigit() {
+                    if self.options.is_allowed(Rule::Escape) {
+                        self.end_high_surrogate()?;
+                        self.escape_backslash()?;
+                        return Ok(Step::Reprocess);
+                    }
+                    return Err(self.invalid());
+                }
+                self.count_string_byte()?;
+                hex[usize::from(len)] = c;
+                self.state = if len == 3 {
+                    self.write_unicode(hex, self.pos.offset - 5)?;
+                    State::String { key }
+                } else {
+                    State::Unicode {
+                        key,
+                        hex,
+                        len: len + 1,
+                    }
+                };
+                Ok(Step::Consumed)
+            }
+            State::Hex { key, mut hex, len } => {
+                if !c.is_ascii_hexdigit() {
+                    // Rule::Escape is allowed in this state.
+                    self.escape_backslash()?;
+                    return Ok(Step::Reprocess);
+                }
+                self.count_string_byte()?;
+                hex[usize::from(len)] = c;
+                self.state = if len == 1 {
+                    let at = self.output.offset();
+                    self.output.write_all(b"\\u00")?;
+                    self.output.write_all(&hex)?;
+                    self.record(
+                        FixKind::EscapeReplaced,
+                        Span::new(self.pos.offset - 3, self.pos.offset + 1),
+                        Span::new(at, at + 6),
+                    );
+                    State::String { key }
+                } else {
+                    State::Hex {
+                        key,
+                        hex,
+                        len: len + 1,
+                    }
+                };
+                Ok(Step::Consumed)
+            }
+            State::Minus
+            | State::Plus
+            | State::Zero { .. }
+            | State::IntegerDigits
+            | State::LeadingPoint
+            | State::Fraction
+            | State::FractionDigits
+            | State::Exponent
+            | State::ExponentSign
+            | State::ExponentDigits
+            | State::Radix { .. } => self.step_number(c),
+            State::Open
+            | State::OpenComma
+            | State::Key
+            | State::BareKey
+            | State::AfterKey { .. }
+            | State::AfterValue { .. }
+            | State::AfterComma { .. } => self.step_container(c),
+            State::End => {
+                if is_ws(c) {
+                    self.output.write_all(&[c])?;
+                    Ok(Step::Consumed)
+                } else {
+                    Err(SyntaxError::TrailingData { pos: self.pos }.into())
+                }
+            }
+        }
+    }
+
+    fn step_value(&mut self, c: u8) -> StepResult {
+        match c {
+            c if is_word_start(c) && self.options.is_allowed(Rule::Literal) => {
+                self.start_word(b"");
+                return Ok(Step::Reprocess);
+            }
+            b'n' => self.state = State::Literal(b"ull"),
+            b't' => self.state = State::Literal(b"rue"),
+            b'f' => self.state = State::Literal(b"alse"),
+            b'{' => self.push_container(Container::Object)?,
+            b'[' => self.push_container(Container::Array)?,
+            b'"' => return self.start_string(false, c),
+            b'\'' if self.options.is_allowed(Rule::SingleQuote) => {
+                return self.start_string(false, c);
+            }
+            b'-' => {
+                // The sign is written with the next digit because it may be a part of
+                // `-Infinity`.
+                self.start_number(State::Minus)?;
+                return Ok(Step::Consumed);
+            }
+            b'+' if self.options.is_allowed(Rule::PlusSign) => {
+                self.start_number(State::Plus)?;
+                let at = self.output.offset();
+                self.record(
+                    FixKind::PlusSignRemoved,
+                    Span::new(self.pos.offset, self.pos.offset + 1),
+                    Span::empty(at),
+                );
+                return Ok(Step::Consumed);
+            }
+            b'.' if self.options.is_allowed(Rule::DecimalPoint) => {
+                self.start_number(State::LeadingPoint)?;
+                self.insert_zero()?;
+            }
+            b'0' => {
+                self.start_number(State::Zero {
+                    start: self.pos.offset,
+                })?;
+                return Ok(Step::Consumed);
+            }
+            b'1'..=b'9' => self.start_number(State::IntegerDigits)?,
+            b',' | b'}'
+                if self.stack.last() == Some(&Container::Object)
+                    && self.options.is_allowed(Rule::MissingValue) =>
+            {
+                let at = self.output.offset();
+                self.output
+                    .write_all(self.options.placeholder.as_str().as_bytes())?;
+                let output_end = self.output.offset();
+                self.record(
+                    FixKind::ValueInserted,
+                    Span::empty(self.pos.offset),
+                    Span::new(at, output_end),
+                );
+                self.end_value(self.pos.offset);
+                return Ok(Step::Reprocess);
+            }
+            _ => return Err(self.invalid()),
+        }
+        self.output.write_all(&[c])?;
+        Ok(Step::Consumed)
+    }
+
+    fn start_string(&mut self, key: bool, quote: u8) -> StepResult {
+        if !key && !self.may_be_misplaced_key() {
+            // Once a string value is started, it is closed rather than discarded.
+            self.output.commit()?;
+        }
+        self.string_start = (self.pos.offset, self.output.offset());
+        self.output.write_all(b"\"")?;
+        self.token_len = 0;
+        self.quote = quote;
+        self.state = State::String { key };
+        Ok(Step::Consumed)
+    }
+
+    /// Starts a literal in [`State::Word`]. `prefix` is the part already consumed.
+    fn start_word(&mut self, prefix: &[u8]) {
+        if prefix.is_empty() {
+            self.token_start = self.pos;
+        }
+        self.word.clear();
+        self.word.extend_from_slice(prefix);
+        self.state = State::Word;
+    }
+
+    fn invalid_word(&self) -> RepairErr {
+        SyntaxError::InvalidValue {
+            pos: self.token_start,
+            expected: Expected::Value,
+        }
+        .into()
+    }
+
+    /// Writes the literal in `word`, replacing it if needed.
+    fn end_word(&mut self) -> ParserResult {
+        let end = self.pos.offset;
+        if let b"null" | b"true" | b"false" = &self.word[..] {
+            self.output.write_all(&self.word)?;
+        } else {
+            let at = self.output.offset();
+            match self.options.find_literal(&self.word) {
+                LiteralLookup::Unknown => return Err(self.invalid_word()),
+                LiteralLookup::NonFinite => {
+                    return Err(SyntaxError::NonFiniteNumber {
+                        pos: self.token_start,
+                    }
+                    .into());
+                }
+                LiteralLookup::Replace(replacement) => {
+                    self.output.write_all(replacement.as_bytes())?;
+                }
+            }
+            let output_end = self.output.offset();
+            self.record(
+                FixKind::LiteralReplaced,
+                Span::new(self.token_start.offset, end),
+                Span::new(at, output_end),
+            );
+        }
+        self.end_value(end);
+        Ok(())
+    }
+
+    fn start_bare_key(&mut self) -> StepResult {
+        self.string_start = (self.pos.offset, self.output.offset());
+        self.output.write_all(b"\"")?;
+        self.token_len = 0;
+        self.state = State::BareKey;
+        Ok(Step::Reprocess)
+    }
+
+    fn start_number(&mut self, state: State) -> ParserResult {
+        self.token_start = self.pos;
+        self.token_len = 0;
+        self.count_number_byte()?;
+        self.state = state;
+        Ok(())
+    }
+
+    fn step_string(&mut self, c: u8, key: bool) -> StepResult {
+        if c != self.quote {
+            self.count_string_byte()?;
+        }
+        self.check_utf8_continuation(c)?;
+        if c != b'\\' {
+            self.end_high_surrogate()?;
+        }
+        match c {
+            c if c == self.quote => {
+                self.output.write_all(b"\"")?;
+                self.record_single_quotes(self.pos.offset + 1);
+                if key {
+                    self.state = State::AfterKey {
+                        key_end: self.pos.offset + 1,
+                    };
+                } else {
+                    self.string_end = self.pos.offset + 1;
+                    self.end_value(self.string_end);
+                }
+            }
+            b'\\' => self.state = State::Escape { key },
+            b'"' => {
+                // A double quote in a single-quoted string.
+                self.output.write_all(b"\\\"")?;
+            }
+            c if c < 0x20 && self.options.is_allowed(Rule::ControlCharacter) => {
+                let at = self.output.offset();
+                match c {
+                    b'\n' => self.output.write_all(b"\\n")?,
+                    b'\t' => self.output.write_all(b"\\t")?,
+                    b'\r' => self.output.write_all(b"\\r")?,
+                    c => write!(self.output, "\\u{:04X}", c)?,
+                }
+                let output_end = self.output.offset();
+                self.record(
+                    FixKind::ControlCharacterEscaped,
+                    Span::new(self.pos.offset, self.pos.offset + 1),
+                    Span::new(at, output_end),
+                );
+            }
+            c if c < 0x20 => {
+                // A raw byte less than 0x20 cannot be embedded in string.
+                return Err(SyntaxError::InvalidValue {
+                    pos: self.pos,
+                    expected: Expected::StringChar,
+                }
+                .into());
+            }
+            c => self.write_utf8_byte(c)?,
+        }
+        Ok(Step::Consumed)
+    }
+
+    /// Reports the UTF-8 sequence held in `utf8` as invalid unless `c` continues it.
+    fn check_utf8_continuation(&mut self, c: u8) -> ParserResult {
+        if !self.utf8.is_empty() && !utf8_continues(&self.utf8, c) {
+            self.invalid_utf8(self.pos.offset - self.utf8.len() as u64, self.pos.offset)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a byte of a string or an unquoted key. A multibyte character is held in `utf8` until
+    /// all of its bytes are read.
+    fn write_utf8_byte(&mut self, c: u8) -> ParserResult {
+        if self.utf8.is_empty() {
+            match utf8_len(c) {
+                Some(1) => return Ok(self.output.write_all(&[c])?),
+                Some(_) => {}
+                None => return self.invalid_utf8(self.pos.offset, self.pos.offset + 1),
+            }
+        }
+        self.utf8.push(c);
+        if Some(self.utf8.len()) == utf8_len(self.utf8[0]) {
+            self.output.write_all(&self.utf8)?;
+            self.utf8.clear();
+        }
+        Ok(())
+    }
+
+    /// Rejects a non-ASCII character in an unquoted key unless it is alphabetic or numeric. `c` is
+    /// the byte following the incomplete UTF-8 sequence held in `utf8`.
+    fn check_key_char(&self, c: u8) -> ParserResult {
+        let len = self.utf8.len();
+        if self.utf8.first().and_then(|&lead| utf8_len(lead)) != Some(len + 1) {
+            return Ok(());
+        }
+        let mut bytes = [0; 4];
+        bytes[..len].copy_from_slice(&self.utf8);
+        bytes[len] = c;
+        let Ok(s) = std::str::from_utf8(&bytes[..=len]) else {
+            return Ok(());
+        };
+        if s.chars().all(char::is_alphanumeric) {
+            return Ok(());
+        }
+        let start = self.pos.offset - len as u64;
+        let expected = if start == self.string_start.0 {
+            Expected::Key
+        } else {
+            Expected::Byte(b':')
+        };
+        Err(SyntaxError::InvalidValue {
+            pos: self.position_at(start),
+            expected,
+        }
+        .into())
+    }
+
+    /// Returns the position of the input offset `offset` on the current line.
+    fn position_at(&self, offset: u64) -> Position {
+        Position {
+            offset,
+            line: self.pos.line,
+            column: self.pos.column - (self.pos.offset - offset),
+        }
+    }
+
+    /// Replaces or reports the invalid UTF-8 sequence in the input range `start..end`.
+    fn invalid_utf8(&mut self, start: u64, end: u64) -> ParserResult {
+        self.utf8.clear();
+        if !self.options.is_allowed(Rule::InvalidUtf8) {
+            return Err(SyntaxError::InvalidUtf8 {
+                pos: self.position_at(start),
+            }
+            .into());
+        }
+        let at = self.output.offset();
+        match self.options.utf8_replacement {
+            Utf8Replacement::Char => self.output.write_all("\u{FFFD}".as_bytes())?,
+            Utf8Replacement::Escape => self.output.write_all(b"\\uFFFD")?,
+        }
+        let output_end = self.output.offset();
+        self.record(
+            FixKind::InvalidUtf8Replaced,
+            Span::new(start, end),
+            Span::new(at, output_end),
+        );
+        Ok(())
+    }
+
+    fn step_escape(&mut self, c: u8, key: bool) -> StepResult {
+        if c != b'u' {
+            self.end_high_surrogate()?;
+        }
+        match c {
+            b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {
+                self.count_string_byte()?;
+                self.output.write_all(&[b'\\', c])?;
+                self.state = State::String { key };
+            }
+            b'\'' if self.quote == b'\'' => {
+                // A single quote needs no escaping in a double-quoted string.
+                self.count_string_byte()?;
+                self.output.write_all(b"'")?;
+                self.state = State::String { key };
+            }
+            b'u' => {
+                self.count_string_byte()?;
+                self.state = State::Unicode {
+                    key,
+                    hex: [0; 4],
+                    len: 0,
+                };
+            }
+            b'x' if self.options.is_allowed(Rule::Escape) => {
+                self.count_string_byte()?;
+                self.state = State::Hex {
+                    key,
+                    hex: [0; 2],
+                    len: 0,
+                };
+            }
+            b'\'' if self.options.is_allowed(Rule::Escape) => {
+                self.count_string_byte()?;
+                let at = self.output.offset();
+                self.output.write_all(b"'")?;
+                self.record(
+                    FixKind::EscapeReplaced,
+                    Span::new(self.pos.offset - 1, self.pos.offset + 1),
+                    Span::new(at, at + 1),
+                );
+                self.state = State::String { key };
+            }
+            _ if self.options.is_allowed(Rule::Escape) => {
+                self.escape_backslash()?;
+                return Ok(Step::Reprocess);
+            }
+            _ => return Err(self.invalid()),
+        }
+        Ok(Step::Consumed)
+    }
+
+    /// Writes the `\uXXXX` escape at the input offset `start`. If surrogates are checked, a high
+    /// surrogate is held back until the next escape, and an unpaired one is replaced or reported.
+    fn write_unicode(&mut self, hex: [u8; 4], start: u64) -> ParserResult {
+        if !self.options.check_surrogates && !self.options.is_allowed(Rule::LoneSurrogate) {
+            self.output.write_all(b"\\u")?;
+            self.output.write_all(&hex)?;
+            return Ok(());
+        }
+        let code = hex.iter().fold(0, |code, &c| {
+            code * 16 + char::from(c).to_digit(16).expect("checked by the caller")
+        });
+        match code {
+            0xDC00..=0xDFFF if self.high_surrogate.is_some() => {
+                let (high, _) = self.high_surrogate.take().expect("checked by the guard");
+                self.output.write_all(b"\\u")?;
+                self.output.write_all(&high)?;
+                self.output.write_all(b"\\u")?;
+                self.output.write_all(&hex)?;
+            }
+            0xD800..=0xDBFF => {
+                self.end_high_surrogate()?;
+                self.high_surrogate = Some((hex, start));
+            }
+            0xDC00..=0xDFFF => self.lone_surrogate(start)?,
+            _ => {
+                self.end_high_surrogate()?;
+                self.output.write_all(b"\\u")?;
+                self.output.write_all(&hex)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces or reports the held high surrogate, if any, which turned out to be unpaired.
+    fn end_high_surrogate(&mut self) -> ParserResult {
+        match self.high_surrogate.take() {
+            Some((_, start)) => self.lone_surrogate(start),
+            None => Ok(()),
+        }
+    }
+
+    fn lone_surrogate(&mut self, start: u64) -> ParserResult {
+        if !self.options.is_allowed(Rule::LoneSurrogate) {
+            return Err(SyntaxError::LoneSurrogate {
+                pos: self.position_at(start),
+            }
+            .into());
+        }
+        let at = self.output.offset();
+        self.output.write_all(b"\\uFFFD")?;
+        self.record(
+            FixKind::LoneSurrogateReplaced,
+            Span::new(start, start + 6),
+            Span::new(at, at + 6),
+        );
+        Ok(())
+    }
+
+    /// Escapes the backslash of the incomplete escape sequence being processed and writes the
+    /// bytes after it as they are.
+    fn escape_backslash(&mut self) -> ParserResult {
+        let (key, rest) = match self.state {
+            State::Escape { key } => (key, Vec::new()),
+            State::Unicode { key, hex, len } => (key, [b"u", &hex[..usize::from(len)]].concat()),
+            State::Hex { key, hex, len } => (key, [b"x", &hex[..usize::from(len)]].concat()),
+            _ => unreachable!("not in an escape sequence"),
+        };
+        let start = self.pos.offset - 1 - rest.len() as u64;
+        let at = self.output.offset();
+        self.output.write_all(b"\\\\")?;
+        self.record(
+            FixKind::EscapeReplaced,
+            Span::new(start, start + 1),
+            Span::new(at, at + 2),
+        );
+        self.output.write_all(&rest)?;
+        self.state = State::String { key };
+        Ok(())
+    }
+
+    fn step_number(&mut self, c: u8) -> StepResult {
+        let next = match (self.state, c) {
+            (State::Minus | State::Plus, b'0') => {
+                self.write_sign()?;
+                State::Zero {
+                    start: self.pos.offset,
+                }
+            }
+            (State::Minus | State::Plus, b'1'..=b'9') => {
+                self.write_sign()?;
+                State::IntegerDigits
+            }
+            (State::Minus | State::Plus, b'.') if self.options.is_allowed(Rule::DecimalPoint) => {
+                self.write_sign()?;
+                self.insert_zero()?;
+                State::LeadingPoint
+            }
+            (State::Minus, c) if is_word_start(c) && self.options.is_allowed(Rule::Literal) => {
+                self.start_word(b"-");
+                return Ok(Step::Reprocess);
+            }
+            (State::Zero { start }, b'0'..=b'9') if self.options.is_allowed(Rule::LeadingZero) => {
+                self.remove_leading_zero(start);
+                if c == b'0' {
+                    State::Zero {
+                        start: self.pos.offset,
+                    }
+                } else {
+                    State::IntegerDigits
+                }
+            }
+            (State::Zero { start }, b'x' | b'X' | b'o' | b'O' | b'b' | b'B')
+                if self.options.is_allowed(Rule::Radix) =>
+            {
+                State::Radix {
+                    start,
+                    radix: match c.to_ascii_lowercase() {
+                        b'x' => 16,
+                        b'o' => 8,
+                        _ => 2,
+                    },
+                    value: None,
+                }
+            }
+            (
+                State::Radix {
+                    start,
+                    radix,
+                    value,
+                },
+                c,
+            ) if char::from(c).is_digit(radix) => {
+                let digit = char::from(c).to_digit(radix).expect("checked by the guard");
+                let value = value
+                    .unwrap_or(0)
+                    .checked_mul(u128::from(radix))
+                    .and_then(|v| v.checked_add(u128::from(digit)))
+                    .ok_or(SyntaxError::UnrepresentableNumber {
+                        pos: self.token_start,
+                    })?;
+                State::Radix {
+                    start,
+                    radix,
+                    value: Some(value),
+                }
+            }
+            (State::Zero { .. }, b'.') => {
+                self.output.write_all(b"0")?;
+                State::Fraction
+            }
+            (State::IntegerDigits, b'0'..=b'9') => State::IntegerDigits,
+            (State::IntegerDigits, b'.') => State::Fraction,
+            (State::LeadingPoint | State::Fraction | State::FractionDigits, b'0'..=b'9') => {
+                State::FractionDigits
+            }
+            (State::Fraction, b'e' | b'E') if self.options.is_allowed(Rule::DecimalPoint) => {
+                self.insert_zero()?;
+                State::Exponent
+            }
+            (State::Zero { .. }, b'e' | b'E') => {
+                self.output.write_all(b"0")?;
+                State::Exponent
+            }
+            (State::IntegerDigits | State::FractionDigits, b'e' | b'E') => State::Exponent,
+            (State::Exponent, b'+' | b'-') => State::ExponentSign,
+            (State::Exponent | State::ExponentSign | State::ExponentDigits, b'0'..=b'9') => {
+                State::ExponentDigits
+            }
+            // A digit or a letter the radix cannot use, e.g., `0b12` or `0xFFG`.
+            (State::Radix { .. }, c) if c.is_ascii_alphanumeric() => return Err(self.invalid()),
+            _ if self.number_can_end() => {
+                // The number has ended. The byte belongs to the next token.
+                self.end_number()?;
+                return Ok(Step::Reprocess);
+            }
+            _ => return Err(self.invalid()),
+        };
+        self.count_number_byte()?;
+        // A zero and the digits of a radix literal are written later.
+        if !matches!(next, State::Zero { .. } | State::Radix { .. }) {
+            self.output.write_all(&[c])?;
+        }
+        self.state = next;
+        Ok(Step::Consumed)
+    }
+
+    /// Returns whether the number being processed is complete or can be completed.
+    fn number_can_end(&self) -> bool {
+        match self.state {
+            State::Zero { .. }
+            | State::IntegerDigits
+            | State::FractionDigits
+            | State::ExponentDigits => true,
+            State::Radix { value, .. } => value.is_some(),
+            State::Fraction => self.options.is_allowed(Rule::DecimalPoint),
+            State::Exponent | State::ExponentSign => self.options.is_allowed(Rule::Exponent),
+            _ => false,
+        }
+    }
+
+    /// Writes the rest of the number being processed and moves to the state after it.
+    fn end_number(&mut self) -> ParserResult {
+        match self.state {
+            State::Zero { .. } => self.output.write_all(b"0")?,
+            State::Radix {
+                start,
+                value: Some(value),
+                ..
+            } => {
+                let at = self.output.offset();
+                self.output.write_all(value.to_string().as_bytes())?;
+                let output_end = self.output.offset();
+                self.record(
+                    FixKind::RadixConverted,
+                    Span::new(start, self.pos.offset),
+                    Span::new(at, output_end),
+                );
+            }
+            State::Fraction | State::Exponent | State::ExponentSign => self.insert_zero()?,
+            _ => {}
+        }
+        self.end_value(self.pos.offset);
+        Ok(())
+    }
+
+    fn write_sign(&mut self) -> ParserResult {
+        // The plus sign is removed.
+        if let State::Minus = self.state {
+            self.output.write_all(b"-")?;
+        }
+        Ok(())
+    }
+
+    fn insert_zero(&mut self) -> ParserResult {
+        let at = self.output.offset();
+        self.output.write_all(b"0")?;
+        self.record(
+            FixKind::ZeroInserted,
+            Span::empty(self.pos.offset),
+            Span::new(at, at + 1),
+        );
+        Ok(())
+    }
+
+    /// Removes the leading zero at the input offset `start`. Consecutive zeros are reported as a
+    /// single fix.
+    fn remove_leading_zero(&mut self, start: u64) {
+        if let Some(last) = self.fixes.last_mut()
+            && last.kind == FixKind::LeadingZeroRemoved
+            && last.input.end == start
+        {
+            last.input.end = start + 1;
+            return;
+        }
+        let at = self.output.offset();
+        self.record(
+            FixKind::LeadingZeroRemoved,
+            Span::new(start, start + 1),
+            Span::empty(at),
+        );
+    }
+
+    fn step_container(&mut self, c: u8) -> StepResult {
+        let container = self.container();
+        match self.state {
+            State::Open => match c {
+                c if is_ws(c) => self.output.write_all(&[c])?,
+                c if c == container.closer() => self.close(c)?,
+                b',' if self.options.is_allowed(Rule::TrailingComma) => {
+                    self.remove_trailing_comma(self.pos.offset);
+                    self.state = State::OpenComma;
+                }
+                b'"' | b'\''
+                    if container == Container::Object
+                        && (c == b'"' || self.options.is_allowed(Rule::SingleQuote)) =>
+                {
+                    self.start_item(self.pos.offset)?;
+                    return self.start_string(true, c);
+                }
+                c if container == Container::Object
+                    && is_ident(c)
+                    && self.options.is_allowed(Rule::UnquotedKey) =>
+                {
+                    self.start_item(self.pos.offset)?;
+                    return self.start_bare_key();
+                }
+                b']' | b'}' if self.options.is_allowed(Rule::MismatchedBracket) => {
+                    return self.close_mismatched(c);
+                }
+                _ if container == Container::Object => return Err(self.invalid()),
+                _ => {
+                    self.start_item(self.pos.offset)?;
+                    self.next_item();
+                    return Ok(Step::Reprocess);
+                }
+            },
+            State::OpenComma => match c {
+                c if is_ws(c) => self.output.write_all(&[c])?,
+                c if c == container.closer() => self.close(c)?,
+                b']' | b'}' if self.options.is_allowed(Rule::MismatchedBracket) => {
+                    return self.close_mismatched(c);
+                }
+                _ => return Err(self.invalid()),
+            },
+            State::Key => match c {
+                c if is_ws(c) => self.output.write_all(&[c])?,
+                b'"' => return self.start_string(true, c),
+                b'\'' if self.options.is_allowed(Rule::SingleQuote) => {
+                    return self.start_string(true, c);
+                }
+                c if is_ident(c) && self.options.is_allowed(Rule::UnquotedKey) => {
+                    return self.start_bare_key();
+                }
+                _ => return Err(self.invalid()),
+            },
+            State::BareKey => match c {
+                c if is_ident(c) => {
+                    self.count_string_byte()?;
+                    self.check_utf8_continuation(c)?;
+                    self.check_key_char(c)?;
+                    self.write_utf8_byte(c)?;
+                }
+                _ => {
+                    self.check_utf8_continuation(c)?;
+                    self.output.write_all(b"\"")?;
+                    let (input_start, output_start) = self.string_start;
+                    let output_end = self.output.offset();
+                    self.record(
+                        FixKind::KeyQuoted,
+                        Span::new(input_start, self.pos.offset),
+                        Span::new(output_start, output_end),
+                    );
+                    self.state = State::AfterKey {
+                        key_end: self.pos.offset,
+                    };
+                    return Ok(Step::Reprocess);
+                }
+            },
+            State::AfterKey { key_end } => match c {
+                c if is_ws(c) => self.ws.push(c),
+                b':' => {
+                    self.flush_ws()?;
+                    self.output.write_all(b":")?;
+                    self.state = State::Value;
+                }
+                b',' | b'}' if self.options.is_allowed(Rule::LoneKey) => {
+                    return self.repair_lone_key(c, key_end);
+                }
+                _ if self.options.is_allowed(Rule::MissingColon) => {
+                    let at = self.output.offset();
+                    self.output.write_all(b":")?;
+                    self.record(
+                        FixKind::ColonInserted,
+                        Span::empty(key_end),
+                        Span::new(at, at + 1),
+                    );
+                    self.flush_ws()?;
+                    self.state = State::Value;
+                    return Ok(Step::Reprocess);
+                }
+                _ => return Err(self.invalid()),
+            },
+            State::AfterValue { value_end } => match c {
+                c if is_ws(c) => self.ws.push(c),
+                c if c == container.closer() => {
+                    self.flush_ws()?;
+                    self.close(c)?;
+                }
+                b',' => {
+                    self.flush_ws()?;
+                    self.state = State::AfterComma {
+                        comma: self.pos.offset,
+                    };
+                }
+                b']' | b'}' if self.options.is_allowed(Rule::MismatchedBracket) => {
+                    self.flush_ws()?;
+                    return self.close_mismatched(c);
+                }
+                b':' if self.may_be_misplaced_key()
+                    && value_end == self.string_end
+                    && self.output.is_holding() =>
+                {
+                    return self.close_before_key();
+                }
+                _ if self.options.is_allowed(Rule::MissingComma) => {
+                    self.start_item(value_end)?;
+                    self.insert_missing_comma(value_end)?;
+                    self.flush_ws()?;
+                    self.next_item();
+                    return Ok(Step::Reprocess);
+                }
+                _ => return Err(self.invalid()),
+            },
+            State::AfterComma { comma } => match c {
+                c if is_ws(c) => self.ws.push(c),
+                c if c == container.closer() && self.options.is_allowed(Rule::TrailingComma) => {
+                    self.remove_trailing_comma(comma);
+                    self.flush_ws()?;
+                    self.close(c)?;
+                }
+                b']' | b'}'
+                    if self.options.is_allowed(Rule::TrailingComma)
+                        && self.options.is_allowed(Rule::MismatchedBracket) =>
+                {
+                    self.remove_trailing_comma(comma);
+                    self.flush_ws()?;
+                    return self.close_mismatched(c);
+                }
+                _ => {
+                    self.start_item(comma)?;
+                    self.output.write_all(b",")?;
+                    self.flush_ws()?;
+                    self.next_item();
+                    return Ok(Step::Reprocess);
+                }
+            },
+            _ => unreachable!("not a container state"),
+        }
+        Ok(Step::Consumed)
+    }
+
+    /// Completes or removes the key that ended at `key_end` and is followed by `c`, which is a
+    /// comma or a closing brace.
+    fn repair_lone_key(&mut self, c: u8, key_end: u64) -> StepResult {
+        if let LoneKey::Fill = self.options.lone_key {
+            let at = self.output.offset();
+            self.output.write_all(b":")?;
+            self.output
+                .write_all(self.options.placeholder.as_str().as_bytes())?;
+            let output_end = self.output.offset();
+            self.record(
+                FixKind::LoneKeyCompleted,
+                Span::empty(key_end),
+                Span::new(at, output_end),
+            );
+            self.flush_ws()?;
+            self.end_value(key_end);
+            return Ok(Step::Reprocess);
+        }
+        // The key has been held back by `start_item`.
+        let (start, fixes) = self.item_start;
+        let member = self.output.take_held();
+        self.fixes.truncate(fixes);
+        self.ws.clear();
+        self.ws_fixes.clear();
+        let at = self.output.offset();
+        if member.first() == Some(&b',') {
+            // The comma before the key is removed with it.
+            self.record(
+                FixKind::LoneKeyRemoved,
+                Span::new(start, self.pos.offset),
+                Span::empty(at),
+            );
+            self.state = State::AfterValue { value_end: start };
+            Ok(Step::Reprocess)
+        } else if c == b',' {
+            // The key is the first member. The comma after it is removed instead.
+            self.record(
+                FixKind::LoneKeyRemoved,
+                Span::new(start, self.pos.offset + 1),
+                Span::empty(at),
+            );
+            self.state = State::Open;
+            Ok(Step::Consumed)
+        } else {
+            self.record(
+                FixKind::LoneKeyRemoved,
+                Span::new(start, self.pos.offset),
+                Span::empty(at),
+            );
+            self.state = State::Open;
+            Ok(Step::Reprocess)
+        }
+    }
+
+    /// Closes the current container on `closer`, which is not its closing bracket. If an outer
+    /// container matches it, the closing bracket of the current one is inserted and `closer` is
+    /// processed again. Otherwise, `closer` is replaced.
+    fn close_mismatched(&mut self, closer: u8) -> StepResult {
+        let container = self.container();
+        let outer = &self.stack[..self.stack.len() - 1];
+        let at = self.output.offset();
+        if outer.iter().any(|k| k.closer() == closer) {
+            self.output.write_all(&[container.closer()])?;
+            self.record(
+                FixKind::CloserInserted,
+                Span::empty(self.pos.offset),
+                Span::new(at, at + 1),
+            );
+            self.stack.pop();
+            self.end_value(self.pos.offset);
+            Ok(Step::Reprocess)
+        } else {
+            self.record(
+                FixKind::CloserReplaced,
+                Span::new(self.pos.offset, self.pos.offset + 1),
+                Span::new(at, at + 1),
+            );
+            self.close(container.closer())?;
+            Ok(Step::Consumed)
+        }
+    }
+
+    /// Closes the current array before its last element, which is followed by a colon and thus
+    /// is a key of the parent object.
+    fn close_before_key(&mut self) -> StepResult {
+        let (start, fixes) = self.item_start;
+        let element = self.output.take_held();
+        let at = self.output.offset();
+        self.output.write_all(b"]")?;
+        let mut inserted = vec![Fix {
+            kind: FixKind::CloserInserted,
+            input: Span::empty(start),
+            output: Span::new(at, at + 1),
+        }];
+        if element.first() != Some(&b',') {
+            // The key was the first element.
+            self.output.write_all(b",")?;
+            inserted.push(Fix {
+                kind: FixKind::MissingCommaInserted,
+                input: Span::empty(start),
+                output: Span::new(at + 1, at + 2),
+            });
+        }
+        let shift = inserted.len() as u64;
+        for fix in &mut self.fixes[fixes..] {
+            fix.output = Span::new(fix.output.start + shift, fix.output.end + shift);
+        }
+        self.fixes.splice(fixes..fixes, inserted);
+        self.output.write_all(&element)?;
+        self.stack.pop();
+        self.flush_ws()?;
+        self.state = State::AfterKey {
+            key_end: self.string_end,
+        };
+        Ok(Step::Reprocess)
+    }
+
+    fn close(&mut self, closer: u8) -> ParserResult {
+        self.output.write_all(&[closer])?;
+        self.stack.pop();
+        self.end_value(self.pos.offset + 1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn repair(input: impl AsRef<[u8]>) -> (super::RepairResult, String) {
+        let mut output = Vec::new();
+        let result = super::repair(input.as_ref(), &mut output);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    fn repair_with(
+        options: super::RepairOptions,
+        input: impl AsRef<[u8]>,
+    ) -> (super::RepairResult, String) {
+        let mut output = Vec::new();
+        let result = super::repair_with(&options, input.as_ref(), &mut output);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_repair_invalid() {
+        assert!(repair(r#"foo"#).0.is_err());
+        assert!(repair(r#"{{}"#).0.is_err());
+        assert!(repair(r#"[]]"#).0.is_err());
+        assert!(repair(r#"[,,]"#).0.is_err());
         assert!(repair(r#"[,,,]"#).0.is_err());
         assert!(repair(r#"{,,}"#).0.is_err());
         assert!(repair(r#"{,,,}"#).0.is_err());
     }
 
     #[test]
-    fn test_repair_valid() {
-        {
-            let s = r#"null"#;
-            let (res, out) = repair(s);
-            assert!(res.is_ok());
-            assert_eq!(s, out);
+    fn test_repair_valid() {
+        {
+            let s = r#"null"#;
+            let (res, out) = repair(s);
+            assert!(res.is_ok());
+            assert_eq!(s, out);
+        }
+        {
+            let s = r#" true"#;
+            let (res, out) = repair(s);
+            assert!(res.is_ok());
+            assert_eq!(s, out);
+        }
+        {
+            let s = r#" false "#;
+            let (res, out) = repair(s);
+            assert!(res.is_ok());
+            assert_eq!(s, out);
+        }
+        {
+            let s = r#" 123.0e-1 "#;
+            let (res, out) = repair(s);
+            assert!(res.is_ok());
+            assert_eq!(s, out);
+        }
+        {
+            let s = r#""foo\"bar\"""#;
+            let (res, out) = repair(s);
+            assert!(res.is_ok());
+            assert_eq!(s, out);
+        }
+    }
+
+    #[test]
+    fn test_repair_repaired() {
+        {
+            let s = r#"[  , ]"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!("[   ]", out);
+        }
+        {
+            let s = r#"[   1 ,  ]"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!("[   1   ]", out);
+        }
+        {
+            let s = r#"[1   2  ]"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!("[1,   2  ]", out);
+        }
+        {
+            let s = r#"[1   2  ,]"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!("[1,   2  ]", out);
+        }
+        {
+            let s = r#"{  , }"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!(r#"{   }"#, out);
+        }
+        {
+            let s = r#"{   "a":1 ,  }"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!(r#"{   "a":1   }"#, out);
+        }
+        {
+            let s = r#"{"a":1   "b":2  }"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!(r#"{"a":1,   "b":2  }"#, out);
+        }
+        {
+            let s = r#"{"a":1   "b":2  ,}"#;
+            let (res, out) = repair(s);
+            assert!(matches!(res, Ok(super::RepairOk::Repaired(_))));
+            assert_eq!(r#"{"a":1,   "b":2  }"#, out);
+        }
+    }
+
+    #[test]
+    fn test_repair_error_position() {
+        {
+            let (res, _) = repair("[\n  1,\n  tru ]");
+            let Err(super::RepairErr::Invalid(err)) = res else {
+                panic!("expected syntax error");
+            };
+            assert!(matches!(
+                err,
+                super::SyntaxError::InvalidValue {
+                    expected: super::Expected::Byte(b'e'),
+                    ..
+                }
+            ));
+            let pos = err.position();
+            assert_eq!((pos.offset, pos.line, pos.column), (12, 3, 6));
+        }
+        {
+            let (res, _) = repair(r#"{"a": [1, 2"#);
+            let Err(super::RepairErr::Invalid(err)) = res else {
+                panic!("expected syntax error");
+            };
+            assert!(matches!(
+                err,
+                super::SyntaxError::UnexpectedEof {
+                    expected: super::Expected::OneOf(b",]"),
+                    ..
+                }
+            ));
+            let pos = err.position();
+            assert_eq!((pos.offset, pos.line, pos.column), (11, 1, 12));
+        }
+        {
+            let (res, _) = repair("1 2");
+            let Err(super::RepairErr::Invalid(err)) = res else {
+                panic!("expected syntax error");
+            };
+            assert!(matches!(err, super::SyntaxError::TrailingData { .. }));
+            assert_eq!(err.position().column, 3);
+        }
+    }
+
+    #[test]
+    fn test_repair_report() {
+        use super::{Fix, FixKind, RepairOk, Span};
+
+        let (res, out) = repair(r#"{"a":[1 2,]  "b":3,}"#);
+        assert_eq!(r#"{"a":[1, 2],  "b":3}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            &[
+                Fix {
+                    kind: FixKind::MissingCommaInserted,
+                    input: Span::empty(7),
+                    output: Span::new(7, 8),
+                },
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(9, 10),
+                    output: Span::empty(10),
+                },
+                Fix {
+                    kind: FixKind::MissingCommaInserted,
+                    input: Span::empty(11),
+                    output: Span::new(11, 12),
+                },
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(18, 19),
+                    output: Span::empty(19),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_deeply_nested() {
+        let depth = 1_000_000;
+        {
+            let s = "[".repeat(depth) + &"]".repeat(depth);
+            let (res, out) = repair(&s);
+            assert!(matches!(res, Ok(super::RepairOk::Valid)));
+            assert_eq!(s, out);
+        }
+        {
+            let s = r#"{"a":["#.repeat(depth);
+            let (res, _) = repair(&s);
+            assert!(matches!(
+                res,
+                Err(super::RepairErr::Invalid(
+                    super::SyntaxError::UnexpectedEof { .. }
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_repair_invalid_number() {
+        assert!(repair("--1").0.is_err());
+        assert!(repair("-").0.is_err());
+        assert!(repair("1.").0.is_err());
+        assert!(repair("1e+").0.is_err());
+    }
+
+    #[test]
+    fn test_repair_limits() {
+        use super::{RepairErr, RepairOptions, SyntaxError};
+
+        assert!(
+            repair_with(RepairOptions::new().max_depth(2), "[[1]]")
+                .0
+                .is_ok()
+        );
+        assert!(matches!(
+            repair_with(RepairOptions::new().max_depth(2), r#"[{"a":[1]}]"#).0,
+            Err(RepairErr::Invalid(SyntaxError::TooDeep { limit: 2, .. }))
+        ));
+
+        assert!(
+            repair_with(RepairOptions::new().max_size(4), "[1] ")
+                .0
+                .is_ok()
+        );
+        assert!(matches!(
+            repair_with(RepairOptions::new().max_size(4), "[1]  ").0,
+            Err(RepairErr::Invalid(SyntaxError::TooLarge { limit: 4, .. }))
+        ));
+
+        assert!(
+            repair_with(RepairOptions::new().max_string_length(4), r#""a\nb""#)
+                .0
+                .is_ok()
+        );
+        assert!(matches!(
+            repair_with(RepairOptions::new().max_string_length(4), r#"{"abcde":1}"#).0,
+            Err(RepairErr::Invalid(SyntaxError::StringTooLong {
+                limit: 4,
+                ..
+            }))
+        ));
+
+        assert!(
+            repair_with(RepairOptions::new().max_number_length(4), "-1e5")
+                .0
+                .is_ok()
+        );
+        assert!(matches!(
+            repair_with(RepairOptions::new().max_number_length(4), "[1.234]").0,
+            Err(RepairErr::Invalid(SyntaxError::NumberTooLong {
+                limit: 4,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_repair_rules() {
+        use super::{RepairErr, RepairOk, RepairOptions, Rule, SyntaxError};
+
+        let no_trailing_comma = || RepairOptions::new().deny(Rule::TrailingComma);
+        assert!(matches!(
+            repair_with(no_trailing_comma(), "[1 2]").0,
+            Ok(RepairOk::Repaired(_))
+        ));
+        assert!(matches!(
+            repair_with(no_trailing_comma(), "[1, 2,]").0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidValue { .. }))
+        ));
+        assert!(matches!(
+            repair_with(no_trailing_comma(), "{,}").0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidValue { .. }))
+        ));
+
+        let no_missing_comma = || RepairOptions::new().deny(Rule::MissingComma);
+        assert!(matches!(
+            repair_with(no_missing_comma(), r#"{"a":1,}"#).0,
+            Ok(RepairOk::Repaired(_))
+        ));
+        assert!(matches!(
+            repair_with(no_missing_comma(), r#"{"a":1 "b":2}"#).0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidValue { .. }))
+        ));
+
+        let options = RepairOptions::new()
+            .deny(Rule::MissingComma)
+            .allow(Rule::MissingComma);
+        assert!(options.is_allowed(Rule::MissingComma));
+        assert_eq!(Rule::from_name("trailing-comma"), Some(Rule::TrailingComma));
+        assert_eq!(Rule::from_name("foo"), None);
+    }
+
+    #[test]
+    fn test_repair_comments() {
+        use super::{CommentMode, Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let input = "// head\n[1, /* a\n b */ 2 // c\n]";
+        assert!(repair(input).0.is_err());
+
+        let strip = RepairOptions::new().allow(Rule::Comment);
+        let (res, out) = repair_with(strip, input);
+        assert_eq!("\n[1,  2 \n]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            &[
+                Fix {
+                    kind: FixKind::CommentRemoved,
+                    input: Span::new(0, 7),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::CommentRemoved,
+                    input: Span::new(12, 22),
+                    output: Span::empty(5),
+                },
+                Fix {
+                    kind: FixKind::CommentRemoved,
+                    input: Span::new(25, 29),
+                    output: Span::empty(8),
+                },
+            ]
+        );
+
+        let whitespace = RepairOptions::new()
+            .allow(Rule::Comment)
+            .comment_mode(CommentMode::Whitespace);
+        let (res, out) = repair_with(whitespace, input);
+        assert_eq!("       \n[1,     \n      2     \n]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert!(report.fixes().iter().all(|fix| fix.input == fix.output));
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::Comment), "1 /**/ // end");
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!("1  ", out);
+        assert!(
+            repair_with(RepairOptions::new().allow(Rule::Comment), "1 /* end")
+                .0
+                .is_err()
+        );
+        assert!(
+            repair_with(RepairOptions::new().allow(Rule::Comment), "1 / 2")
+                .0
+                .is_err()
+        );
+        assert!(
+            repair_with(RepairOptions::new().allow(Rule::Comment), r#""/**/""#)
+                .0
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_repair_truncation() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let options = || RepairOptions::new().allow(Rule::Truncation);
+
+        assert!(repair(r#"{"a": [1"#).0.is_err());
+
+        for (input, expected) in [
+            (
+                r#"{"a": [1, 2, {"b": "hel"#,
+                r#"{"a": [1, 2, {"b": "hel"}]}"#,
+            ),
+            (r#"["a\u00"#, r#"["a"]"#),
+            (r#"["a\"#, r#"["a"]"#),
+            (r#"{"a": 1, "b"#, r#"{"a": 1}"#),
+            (r#"{"a": 1, "b" :"#, r#"{"a": 1}"#),
+            (r#"{"a": {"b": tru"#, r#"{"a": {}}"#),
+            (r#"[1, 2 "#, r#"[1, 2 ]"#),
+            (r#"[1, 2, "#, r#"[1, 2 ]"#),
+            (r#"[1, 2."#, r#"[1]"#),
+            (r#"[1 -"#, r#"[1]"#),
+            (r#"[[["#, r#"[[[]]]"#),
+            (r#""abc"#, r#""abc""#),
+        ] {
+            let (res, out) = repair_with(options(), input);
+            assert!(matches!(res, Ok(RepairOk::Repaired(_))), "{input}");
+            assert_eq!(expected, out, "{input}");
+        }
+
+        assert!(repair_with(options(), "tru").0.is_err());
+        assert!(repair_with(options(), "-").0.is_err());
+
+        let (res, out) = repair_with(options(), r#"[1, {"a": nul"#);
+        assert_eq!("[1, {}]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            &[
+                Fix {
+                    kind: FixKind::IncompleteValueRemoved,
+                    input: Span::new(5, 13),
+                    output: Span::empty(5),
+                },
+                Fix {
+                    kind: FixKind::UnclosedContainerClosed,
+                    input: Span::empty(13),
+                    output: Span::new(5, 6),
+                },
+                Fix {
+                    kind: FixKind::UnclosedContainerClosed,
+                    input: Span::empty(13),
+                    output: Span::new(6, 7),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_single_quotes() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let input = r#"{'a': 'it\'s "b"', "c": ['d']}"#;
+        assert!(repair(input).0.is_err());
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::SingleQuote), input);
+        assert_eq!(r#"{"a": "it's \"b\"", "c": ["d"]}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            &[
+                Fix {
+                    kind: FixKind::SingleQuotesReplaced,
+                    input: Span::new(1, 4),
+                    output: Span::new(1, 4),
+                },
+                Fix {
+                    kind: FixKind::SingleQuotesReplaced,
+                    input: Span::new(6, 17),
+                    output: Span::new(6, 18),
+                },
+                Fix {
+                    kind: FixKind::SingleQuotesReplaced,
+                    input: Span::new(25, 28),
+                    output: Span::new(26, 29),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_unquoted_keys() {
+        use super::{
+            Expected, Fix, FixKind, Position, RepairErr, RepairOk, RepairOptions, Rule, Span,
+            SyntaxError,
+        };
+
+        let input = r#"{foo: 1, $bar_2 :{"baz": 3}, ключ: 4}"#;
+        assert!(repair(input).0.is_err());
+
+        let options = RepairOptions::new().allow(Rule::UnquotedKey);
+        let (res, out) = repair_with(options.clone(), input);
+        assert_eq!(r#"{"foo": 1, "$bar_2" :{"baz": 3}, "ключ": 4}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[..2],
+            [
+                Fix {
+                    kind: FixKind::KeyQuoted,
+                    input: Span::new(1, 4),
+                    output: Span::new(1, 6),
+                },
+                Fix {
+                    kind: FixKind::KeyQuoted,
+                    input: Span::new(9, 15),
+                    output: Span::new(11, 19),
+                },
+            ]
+        );
+        assert_eq!(report.fixes().len(), 3);
+
+        assert!(repair_with(options.clone(), "{a-b: 1}").0.is_err());
+        assert!(repair_with(options.clone(), "[a]").0.is_err());
+
+        // Smart quotes and a no-break space are not identifier characters.
+        assert!(matches!(
+            repair_with(options.clone(), "{\u{201C}a\u{201D}: 1}").0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidValue {
+                pos: Position { offset: 1, .. },
+                expected: Expected::Key,
+            }))
+        ));
+        assert!(matches!(
+            repair_with(options.clone(), "{a\u{201D}: 1}").0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidValue {
+                pos: Position { offset: 2, .. },
+                expected: Expected::Byte(b':'),
+            }))
+        ));
+        assert!(repair_with(options.clone(), "{a\u{A0}b: 1}").0.is_err());
+        assert!(repair_with(options, "{ключ\u{A0}: 1}").0.is_err());
+    }
+
+    #[test]
+    fn test_repair_literals() {
+        use super::{
+            Fix, FixKind, LiteralSpelling, NonFinite, RepairErr, RepairOk, RepairOptions, Rule,
+            Span, SyntaxError,
+        };
+
+        let input = "[True, False, None, undefined, NaN, -Infinity, null, -1]";
+        assert!(repair(input).0.is_err());
+
+        let options = || RepairOptions::new().allow(Rule::Literal);
+        let (res, out) = repair_with(options(), input);
+        assert_eq!("[true, false, null, null, null, null, null, -1]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.fixes().len(), 6);
+        assert_eq!(
+            report.fixes()[5],
+            Fix {
+                kind: FixKind::LiteralReplaced,
+                input: Span::new(36, 45),
+                output: Span::new(32, 36),
+            }
+        );
+
+        let (_, out) = repair_with(options().non_finite(NonFinite::String), input);
+        assert_eq!(
+            r#"[true, false, null, null, "NaN", "-Infinity", null, -1]"#,
+            out
+        );
+        assert!(matches!(
+            repair_with(options().non_finite(NonFinite::Error), input).0,
+            Err(RepairErr::Invalid(SyntaxError::NonFiniteNumber { .. }))
+        ));
+
+        let literal = |options: RepairOptions, spelling: &str, replacement: &str| {
+            let spelling = LiteralSpelling::new(spelling).unwrap();
+            options.literal(spelling, replacement.parse().unwrap())
+        };
+        let (res, out) = repair_with(
+            literal(literal(options(), "nil", "null"), "None", "0"),
+            "[nil, None]",
+        );
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!("[null, 0]", out);
+        let options_without_defaults = || options().default_literals(false);
+        let (_, out) = repair_with(
+            literal(options_without_defaults(), "None", "null"),
+            "[None]",
+        );
+        assert_eq!("[null]", out);
+        assert!(repair_with(options_without_defaults(), "[True]").0.is_err());
+        assert!(
+            repair_with(options_without_defaults(), "[undefined]")
+                .0
+                .is_err()
+        );
+        let (_, out) = repair_with(options_without_defaults(), "[NaN]");
+        assert_eq!("[null]", out);
+        for spelling in ["", "-", "1x", "a-b", "a b", "\u{201C}a"] {
+            assert!(LiteralSpelling::new(spelling).is_none());
         }
-        {
-            let s = r#" true"#;
-            let (res, out) = repair(s);
-            assert!(res.is_ok());
-            assert_eq!(s, out);
+
+        assert!(matches!(
+            repair_with(options(), "[true, null]").0,
+            Ok(RepairOk::Valid)
+        ));
+        assert!(repair_with(options(), "[nil]").0.is_err());
+        assert!(repair_with(options(), "[Nonexistent]").0.is_err());
+        assert!(repair_with(options(), "[-Inf]").0.is_err());
+    }
+
+    #[test]
+    fn test_repair_numbers() {
+        use super::{Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError};
+
+        let input = "[007, +1, .5, 1., 1e, -0x1F, 0o17, 0b101, 0]";
+        assert!(repair(input).0.is_err());
+
+        let options = || {
+            RepairOptions::new()
+                .allow(Rule::LeadingZero)
+                .allow(Rule::PlusSign)
+                .allow(Rule::DecimalPoint)
+                .allow(Rule::Exponent)
+                .allow(Rule::Radix)
+        };
+        let (res, out) = repair_with(options(), input);
+        assert_eq!("[7, 1, 0.5, 1.0, 1e0, -31, 15, 5, 0]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[..2],
+            [
+                Fix {
+                    kind: FixKind::LeadingZeroRemoved,
+                    input: Span::new(1, 3),
+                    output: Span::empty(1),
+                },
+                Fix {
+                    kind: FixKind::PlusSignRemoved,
+                    input: Span::new(6, 7),
+                    output: Span::empty(4),
+                },
+            ]
+        );
+        assert_eq!(
+            report.fixes()[5],
+            Fix {
+                kind: FixKind::RadixConverted,
+                input: Span::new(23, 27),
+                output: Span::new(23, 25),
+            }
+        );
+
+        let (_, out) = repair_with(options(), "[1.e5, -.5, 1e+, 000]");
+        assert_eq!("[1.0e5, -0.5, 1e+0, 0]", out);
+        assert!(matches!(
+            repair_with(options(), "[0.5, 10, -0]").0,
+            Ok(RepairOk::Valid)
+        ));
+        assert!(repair_with(options().deny(Rule::Radix), "0x1F").0.is_err());
+        assert!(repair_with(options(), "0x").0.is_err());
+        for input in ["[0b12]", "0xFFG", "[0o78, 1]", "0b1e1"] {
+            assert!(matches!(
+                repair_with(options(), input).0,
+                Err(RepairErr::Invalid(SyntaxError::InvalidValue { .. }))
+            ));
         }
-        {
-            let s = r#" false "#;
-            let (res, out) = repair(s);
-            assert!(res.is_ok());
-            assert_eq!(s, out);
+        for input in [".", "-.", "+.", "[.]", "[1, ., 2]", "[.e1]"] {
+            assert!(matches!(
+                repair_with(options(), input).0,
+                Err(RepairErr::Invalid(SyntaxError::InvalidValue { .. }))
+                    | Err(RepairErr::Invalid(SyntaxError::UnexpectedEof { .. }))
+            ));
         }
-        {
-            let s = r#" 123.0e-1 "#;
-            let (res, out) = repair(s);
-            assert!(res.is_ok());
-            assert_eq!(s, out);
+        assert!(matches!(
+            repair_with(options(), "0x1000000000000000000000000000000000").0,
+            Err(RepairErr::Invalid(
+                SyntaxError::UnrepresentableNumber { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_repair_control_characters() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let input = "{\"a\tb\": \"x\ny\r\u{1}\"}";
+        assert!(repair(input).0.is_err());
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::ControlCharacter), input);
+        assert_eq!(r#"{"a\tb": "x\ny\r\u0001"}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.fixes().len(), 4);
+        assert_eq!(
+            report.fixes()[3],
+            Fix {
+                kind: FixKind::ControlCharacterEscaped,
+                input: Span::new(13, 14),
+                output: Span::new(16, 22),
+            }
+        );
+    }
+
+    #[test]
+    fn test_repair_escapes() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let input = r#"["\x41", "\'", "C:\Users\new", "\u12"]"#;
+        assert!(repair(input).0.is_err());
+
+        let options = || RepairOptions::new().allow(Rule::Escape);
+        let (res, out) = repair_with(options(), input);
+        assert_eq!(r#"["\u0041", "'", "C:\\Users\new", "\\u12"]"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::EscapeReplaced,
+                    input: Span::new(2, 6),
+                    output: Span::new(2, 8),
+                },
+                Fix {
+                    kind: FixKind::EscapeReplaced,
+                    input: Span::new(10, 12),
+                    output: Span::new(12, 13),
+                },
+                Fix {
+                    kind: FixKind::EscapeReplaced,
+                    input: Span::new(18, 19),
+                    output: Span::new(19, 21),
+                },
+                Fix {
+                    kind: FixKind::EscapeReplaced,
+                    input: Span::new(32, 33),
+                    output: Span::new(34, 36),
+                },
+            ]
+        );
+
+        let (_, out) = repair_with(options().allow(Rule::Truncation), r#"["C:\"#);
+        assert_eq!(r#"["C:\\"]"#, out);
+        assert!(repair_with(options(), r#""C:\"#).0.is_err());
+    }
+
+    #[test]
+    fn test_repair_invalid_utf8() {
+        use super::{
+            Fix, FixKind, Position, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError,
+            Utf8Replacement,
+        };
+
+        let input = b"[\"a\xffb\", \"\xc0\xaf\", \"\xed\xa0\x80\", \"\xe2\x82\", \"\xe2\x82\xac\"]";
+        assert!(matches!(
+            repair(input).0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidUtf8 {
+                pos: Position {
+                    offset: 3,
+                    line: 1,
+                    column: 4,
+                },
+            }))
+        ));
+
+        let options = || RepairOptions::new().allow(Rule::InvalidUtf8);
+        let (res, out) = repair_with(options(), input);
+        assert_eq!(
+            "[\"a\u{FFFD}b\", \"\u{FFFD}\u{FFFD}\", \"\u{FFFD}\u{FFFD}\u{FFFD}\", \"\u{FFFD}\", \"\u{20AC}\"]",
+            out
+        );
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.fixes().len(), 7);
+        assert_eq!(
+            report.fixes()[6],
+            Fix {
+                kind: FixKind::InvalidUtf8Replaced,
+                input: Span::new(22, 24),
+                output: Span::new(34, 37),
+            }
+        );
+
+        let (_, out) = repair_with(
+            options().utf8_replacement(Utf8Replacement::Escape),
+            b"\"a\xffb\"",
+        );
+        assert_eq!(r#""a\uFFFDb""#, out);
+    }
+
+    #[test]
+    fn test_repair_lone_surrogates() {
+        use super::{Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError};
+
+        let input = r#"["\uD83D\uDE00", "\uD800", "\uDC00\uD800", "\uD800\n"]"#;
+        assert!(matches!(repair(input).0, Ok(RepairOk::Valid)));
+        assert!(matches!(
+            repair_with(RepairOptions::new().check_surrogates(true), input).0,
+            Err(RepairErr::Invalid(SyntaxError::LoneSurrogate { .. }))
+        ));
+
+        let options = RepairOptions::new().allow(Rule::LoneSurrogate);
+        let (res, out) = repair_with(options, input);
+        assert_eq!(
+            r#"["\uD83D\uDE00", "\uFFFD", "\uFFFD\uFFFD", "\uFFFD\n"]"#,
+            out
+        );
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.fixes().len(), 4);
+        assert_eq!(
+            report.fixes()[1],
+            Fix {
+                kind: FixKind::LoneSurrogateReplaced,
+                input: Span::new(28, 34),
+                output: Span::new(28, 34),
+            }
+        );
+    }
+
+    #[test]
+    fn test_repair_bom() {
+        use super::{Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError};
+
+        let input = b"\xEF\xBB\xBF[1]";
+        assert!(matches!(
+            repair(input).0,
+            Err(RepairErr::Invalid(SyntaxError::ByteOrderMark { .. }))
+        ));
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::Bom), input);
+        assert_eq!("[1]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::BomRemoved,
+                input: Span::new(0, 3),
+                output: Span::empty(0),
+            }]
+        );
+
+        let (res, out) = repair_with(RepairOptions::new().keep_bom(true), input);
+        assert!(matches!(res, Ok(RepairOk::Valid)));
+        assert_eq!(input, out.as_bytes());
+
+        let utf16le = b"\xFF\xFE[\x00\"\x00\xAC\x20=\xD8\x00\xDE\"\x00]\x00";
+        let (_, out) = repair_with(RepairOptions::new().allow(Rule::Bom), utf16le);
+        assert_eq!("[\"\u{20AC}\u{1F600}\"]", out);
+        let utf16be = b"\xFE\xFF\x00[\x00]";
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::Bom), utf16be);
+        assert_eq!("[]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::Utf16Transcoded,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::BomRemoved,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+            ]
+        );
+        let (res, out) = repair_with(RepairOptions::new().keep_bom(true), b"\xFF\xFE[\x00]\x00");
+        assert_eq!("\u{FEFF}[]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::Utf16Transcoded,
+                input: Span::new(0, 3),
+                output: Span::new(0, 3),
+            }]
+        );
+        assert!(matches!(
+            repair_with(RepairOptions::new().allow(Rule::Bom), b"\xFF\xFE[\x00]").0,
+            Err(RepairErr::Invalid(SyntaxError::InvalidUtf16 { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_repair_extract() {
+        use super::{
+            Extract, Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError,
+        };
+
+        let input = "Result:\n```json\n{\"a\": [1,]}\n```\nSee [note] and [2].";
+        assert!(repair(input).0.is_err());
+
+        let (res, out) = repair_with(RepairOptions::new().extract(Extract::First), input);
+        assert_eq!(r#"{"a": [1]}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.extracted(), [Span::new(16, 27)]);
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(0, 16),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(24, 25),
+                    output: Span::empty(8),
+                },
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(27, 51),
+                    output: Span::empty(10),
+                },
+            ]
+        );
+
+        let (res, out) = repair_with(RepairOptions::new().extract(Extract::All), input);
+        assert_eq!("{\"a\": [1]}\n[2]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.extracted(), [Span::new(16, 27), Span::new(47, 50)]);
+
+        assert!(matches!(
+            repair_with(RepairOptions::new().extract(Extract::First), "[1]").0,
+            Ok(RepairOk::Valid)
+        ));
+        let (res, out) = repair_with(RepairOptions::new().extract(Extract::First), " [1] 42");
+        assert_eq!("[1]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(0, 1),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(4, 7),
+                    output: Span::empty(3),
+                },
+            ]
+        );
+        assert!(
+            repair_with(RepairOptions::new().extract(Extract::First), "[no json")
+                .0
+                .is_err()
+        );
+
+        let (_, out) = repair_with(
+            RepairOptions::new().extract(Extract::First),
+            r#"[x {"a": 1}"#,
+        );
+        assert_eq!(r#"{"a": 1}"#, out);
+
+        // A value in a code block marked as JSON is preferred to the values before it.
+        let input = "see [1] and this:\n```json\n{\"a\": 1}\n```";
+        let (res, out) = repair_with(RepairOptions::new().extract(Extract::First), input);
+        assert_eq!(r#"{"a": 1}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.extracted(), [Span::new(26, 34)]);
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(0, 26),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(34, 38),
+                    output: Span::empty(8),
+                },
+            ]
+        );
+        for (input, expected) in [
+            ("[1] then ```json\n{x\n```", "[1]"),
+            ("[1] [2] ````json [3] [4]", "[3]"),
+            ("[1] ```js\n[2]\n```", "[1]"),
+        ] {
+            let (_, out) = repair_with(RepairOptions::new().extract(Extract::First), input);
+            assert_eq!(expected, out);
         }
-        {
-            let s = r#""foo\"bar\"""#;
-            let (res, out) = repair(s);
-            assert!(res.is_ok());
-            assert_eq!(s, out);
+
+        let options = || RepairOptions::new().extract(Extract::First);
+        let input = b"\xEF\xBB\xBFsee [1]";
+        assert!(matches!(
+            repair_with(options(), input).0,
+            Err(RepairErr::Invalid(SyntaxError::ByteOrderMark { .. }))
+        ));
+        let (res, out) = repair_with(options().allow(Rule::Bom), input);
+        assert_eq!("[1]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.extracted(), [Span::new(7, 10)]);
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::BomRemoved,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::TextRemoved,
+                    input: Span::new(3, 7),
+                    output: Span::empty(0),
+                },
+            ]
+        );
+        let (_, out) = repair_with(options().keep_bom(true), input);
+        assert_eq!("\u{FEFF}[1]", out);
+        let (_, out) = repair_with(options().allow(Rule::Bom), b"\xFF\xFE[\x001\x00]\x00");
+        assert_eq!("[1]", out);
+
+        // Rejected candidates are not scanned again without limit.
+        let input = format!("text {}", r#"{"a": "#.repeat(8000));
+        assert!(
+            repair_with(RepairOptions::new().extract(Extract::First), input)
+                .0
+                .is_err()
+        );
+        let input = "[".repeat(8000);
+        assert!(
+            repair_with(RepairOptions::new().extract(Extract::All), input)
+                .0
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_repair_wrappers() {
+        use super::{Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError};
+
+        let input = r#"callback({"a": 1});"#;
+        assert!(repair(input).0.is_err());
+
+        let options = || RepairOptions::new().allow(Rule::Wrapper);
+        let (res, out) = repair_with(options(), input);
+        assert_eq!(r#"{"a": 1}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::WrapperRemoved,
+                    input: Span::new(0, 9),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::WrapperRemoved,
+                    input: Span::new(17, 19),
+                    output: Span::empty(8),
+                },
+            ]
+        );
+
+        for (input, expected) in [
+            ("window.__DATA__ = [1, 2];", "[1, 2]"),
+            ("var x =\n  {\"a\": 1}", "{\"a\": 1}"),
+            ("export default true;", "true"),
+            ("cb(1)", "1"),
+        ] {
+            let (res, out) = repair_with(options(), input);
+            assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+            assert_eq!(expected, out);
+        }
+        assert!(matches!(
+            repair_with(options(), "null").0,
+            Ok(RepairOk::Valid)
+        ));
+        assert!(repair_with(options(), "cb([1]").0.is_err());
+        assert!(repair_with(options(), "cb([1]) x").0.is_err());
+        assert!(repair_with(options(), "var = 1").0.is_err());
+
+        let input = "cb({\"a\":1}/*x*/);";
+        assert!(matches!(
+            repair_with(options(), input).0,
+            Err(RepairErr::Invalid(SyntaxError::TrailingData { .. }))
+        ));
+        let (res, out) = repair_with(options().allow(Rule::Comment), input);
+        assert_eq!(r#"{"a":1}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[1],
+            Fix {
+                kind: FixKind::WrapperRemoved,
+                input: Span::new(10, 17),
+                output: Span::empty(7),
+            }
+        );
+        for (input, expected) in [
+            ("cb([1]) // done\n;", "[1]"),
+            ("var x = 1 /* a */ ; // b", "1 "),
+            ("cb(1/**/)", "1"),
+        ] {
+            let (_, out) = repair_with(options().allow(Rule::Comment), input);
+            assert_eq!(expected, out);
         }
+        assert!(
+            repair_with(options().allow(Rule::Comment), "cb([1]) /* x")
+                .0
+                .is_err()
+        );
+        assert!(
+            repair_with(options().allow(Rule::Comment), "cb([1]) /x")
+                .0
+                .is_err()
+        );
+
+        let input = b"\xEF\xBB\xBFcallback({\"a\": 1});";
+        let (res, out) = repair_with(options().allow(Rule::Bom), input);
+        assert_eq!(r#"{"a": 1}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::BomRemoved,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::WrapperRemoved,
+                    input: Span::new(3, 12),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::WrapperRemoved,
+                    input: Span::new(20, 22),
+                    output: Span::empty(8),
+                },
+            ]
+        );
+        let (res, out) = repair_with(options().keep_bom(true), input);
+        assert_eq!("\u{FEFF}{\"a\": 1}", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[0],
+            Fix {
+                kind: FixKind::WrapperRemoved,
+                input: Span::new(3, 12),
+                output: Span::empty(3),
+            }
+        );
+        let (_, out) = repair_with(
+            options().allow(Rule::Bom),
+            b"\xFF\xFEc\x00b\x00(\x00[\x00]\x00)\x00",
+        );
+        assert_eq!("[]", out);
+        assert!(matches!(
+            repair_with(options(), input).0,
+            Err(RepairErr::Invalid(SyntaxError::ByteOrderMark { .. }))
+        ));
+
+        assert!(matches!(
+            repair_with(options().max_size(10), format!("cb(1){}", " ".repeat(23))).0,
+            Err(RepairErr::Invalid(SyntaxError::TooLarge { limit: 10, .. }))
+        ));
     }
 
     #[test]
-    fn test_repair_repaired() {
-        {
-            let s = r#"[  , ]"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!("[   ]", out);
+    fn test_repair_mismatched_brackets() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span};
+
+        let input = r#"[1, 2}"#;
+        assert!(repair(input).0.is_err());
+
+        let options = || RepairOptions::new().allow(Rule::MismatchedBracket);
+        let (res, out) = repair_with(options(), input);
+        assert_eq!(r#"[1, 2]"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::CloserReplaced,
+                input: Span::new(5, 6),
+                output: Span::new(5, 6),
+            }]
+        );
+
+        let (res, out) = repair_with(options(), r#"{"a": [1, 2}"#);
+        assert_eq!(r#"{"a": [1, 2]}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::CloserInserted,
+                input: Span::empty(11),
+                output: Span::new(11, 12),
+            }]
+        );
+
+        let (res, out) = repair_with(options().allow(Rule::SingleQuote), r#"{"a": ['x', 'b': 3}"#);
+        assert_eq!(r#"{"a": ["x"], "b": 3}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::SingleQuotesReplaced,
+                    input: Span::new(7, 10),
+                    output: Span::new(7, 10),
+                },
+                Fix {
+                    kind: FixKind::CloserInserted,
+                    input: Span::empty(10),
+                    output: Span::new(10, 11),
+                },
+                Fix {
+                    kind: FixKind::SingleQuotesReplaced,
+                    input: Span::new(12, 15),
+                    output: Span::new(13, 16),
+                },
+            ]
+        );
+
+        for (input, expected) in [
+            (r#"{"a": [1, 2, "b": 3}"#, r#"{"a": [1, 2], "b": 3}"#),
+            (r#"{"a": ["x" "b": 3}"#, r#"{"a": ["x"], "b": 3}"#),
+            (r#"{"a": [1, {"c": 2]}"#, r#"{"a": [1, {"c": 2}]}"#),
+            (r#"[{"a": 1]"#, r#"[{"a": 1}]"#),
+            (r#"{"a": [1, 2,}"#, r#"{"a": [1, 2]}"#),
+            (r#"{]"#, r#"{}"#),
+        ] {
+            let (res, out) = repair_with(options(), input);
+            assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+            assert_eq!(expected, out);
         }
-        {
-            let s = r#"[   1 ,  ]"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!("[   1   ]", out);
+        assert!(matches!(
+            repair_with(options(), r#"{"a": ["x", "b"]}"#).0,
+            Ok(RepairOk::Valid)
+        ));
+        assert!(repair_with(options(), r#"[1, "b": 2]"#).0.is_err());
+        assert!(repair_with(options(), r#"{"a": [1, 2]"#).0.is_err());
+    }
+
+    #[test]
+    fn test_repair_missing_colons_and_values() {
+        use super::{Fix, FixKind, LoneKey, RawValue, RepairOk, RepairOptions, Rule, Span};
+
+        for input in [r#"{"a" 1}"#, r#"{"a": }"#, r#"{"a"}"#] {
+            assert!(repair(input).0.is_err());
         }
-        {
-            let s = r#"[1   2  ]"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!("[1,   2  ]", out);
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::MissingColon), r#"{"a" 1}"#);
+        assert_eq!(r#"{"a": 1}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::ColonInserted,
+                input: Span::empty(4),
+                output: Span::new(4, 5),
+            }]
+        );
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::MissingValue), r#"{"a": }"#);
+        assert_eq!(r#"{"a": null}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::ValueInserted,
+                input: Span::empty(6),
+                output: Span::new(6, 10),
+            }]
+        );
+
+        let (res, out) = repair_with(RepairOptions::new().allow(Rule::LoneKey), r#"{"a"}"#);
+        assert_eq!(r#"{"a":null}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::LoneKeyCompleted,
+                input: Span::empty(4),
+                output: Span::new(4, 9),
+            }]
+        );
+
+        let options = || {
+            RepairOptions::new()
+                .allow(Rule::LoneKey)
+                .lone_key(LoneKey::Drop)
+        };
+        let (res, out) = repair_with(options(), r#"{"x": 1, "a", "b": 2}"#);
+        assert_eq!(r#"{"x": 1, "b": 2}"#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::LoneKeyRemoved,
+                input: Span::new(7, 12),
+                output: Span::empty(7),
+            }]
+        );
+
+        for (input, expected) in [
+            (r#"{"a", "b": 2}"#, r#"{ "b": 2}"#),
+            (r#"{"x": 1, "a" }"#, r#"{"x": 1}"#),
+            (r#"{"a"}"#, r#"{}"#),
+        ] {
+            let (res, out) = repair_with(options(), input);
+            assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+            assert_eq!(expected, out);
         }
-        {
-            let s = r#"[1   2  ,]"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!("[1,   2  ]", out);
+
+        let options = RepairOptions::new()
+            .allow(Rule::MissingValue)
+            .allow(Rule::LoneKey)
+            .placeholder(r#""""#.parse().unwrap());
+        let (res, out) = repair_with(options, r#"{"a":, "b"}"#);
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!(r#"{"a":"", "b":""}"#, out);
+        for placeholder in ["oops", "1 2", "", "[1,]"] {
+            assert!(placeholder.parse::<RawValue>().is_err());
         }
-        {
-            let s = r#"{  , }"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!(r#"{   }"#, out);
+
+        let options = RepairOptions::new().allow(Rule::MissingValue);
+        assert!(repair_with(options.clone(), r#"[1, , 2]"#).0.is_err());
+        assert!(repair_with(options, r#"{"a"}"#).0.is_err());
+    }
+
+    #[test]
+    fn test_repair_lines() {
+        use super::{
+            Fix, FixKind, InvalidLine, RepairErr, RepairOk, RepairOptions, Rule, Span, SyntaxError,
+        };
+
+        fn repair_lines(
+            options: RepairOptions,
+            input: impl AsRef<[u8]>,
+        ) -> (Result<Vec<super::LineResult>, super::RepairErr>, String) {
+            let mut output = Vec::new();
+            let mut lines = Vec::new();
+            let result = super::repair_lines(&options, input.as_ref(), &mut output, |line| {
+                lines.push(line);
+                Ok(())
+            });
+            (result.map(|()| lines), String::from_utf8(output).unwrap())
         }
-        {
-            let s = r#"{   "a":1 ,  }"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!(r#"{   "a":1   }"#, out);
+
+        let input = "{\"a\": 1,}\n\n[1 2]\nnope\n1";
+        assert!(repair(input).0.is_err());
+        let options = RepairOptions::new().invalid_line(InvalidLine::Fail);
+        assert!(repair_lines(options, input).0.is_err());
+
+        let options = RepairOptions::new().invalid_line(InvalidLine::Skip);
+        let (res, out) = repair_lines(options, input);
+        assert_eq!("{\"a\": 1}\n\n[1, 2]\n1", out);
+        let lines = res.unwrap();
+        assert_eq!(
+            lines.iter().map(|l| (l.line, l.input)).collect::<Vec<_>>(),
+            [
+                (1, Span::new(0, 9)),
+                (3, Span::new(11, 16)),
+                (4, Span::new(17, 21)),
+                (5, Span::new(22, 23)),
+            ]
+        );
+        let Ok(RepairOk::Repaired(report)) = &lines[0].result else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::TrailingCommaRemoved,
+                input: Span::new(7, 8),
+                output: Span::empty(7),
+            }]
+        );
+        let Ok(RepairOk::Repaired(report)) = &lines[1].result else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::MissingCommaInserted,
+                input: Span::empty(13),
+                output: Span::new(12, 13),
+            }]
+        );
+        let Err(err) = &lines[2].result else {
+            panic!("expected invalid");
+        };
+        assert_eq!(4, err.position().line);
+        assert!(matches!(lines[3].result, Ok(RepairOk::Valid)));
+
+        let (res, out) = repair_lines(RepairOptions::new(), input);
+        assert_eq!("{\"a\": 1}\n\n[1, 2]\nnope\n1", out);
+        assert_eq!(4, res.unwrap().len());
+
+        let (res, out) = repair_lines(RepairOptions::new(), "[1]\r\n{}\n");
+        assert_eq!("[1]\r\n{}\n", out);
+        assert!(
+            res.unwrap()
+                .iter()
+                .all(|l| matches!(l.result, Ok(RepairOk::Valid)))
+        );
+
+        let options = RepairOptions::new()
+            .allow(Rule::Comment)
+            .invalid_line(InvalidLine::Fail);
+        let (res, out) = repair_lines(options.clone(), "// note\n[1] // one\n  /* x */\n/*");
+        assert_eq!("\n[1] \n  \n", out);
+        assert!(res.is_err());
+        let (res, out) = repair_lines(options, "// note\n[1]\n");
+        assert_eq!("\n[1]\n", out);
+        let lines = res.unwrap();
+        assert_eq!(2, lines.len());
+        let Ok(RepairOk::Repaired(report)) = &lines[0].result else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::CommentRemoved,
+                input: Span::new(0, 7),
+                output: Span::empty(0),
+            }]
+        );
+        assert!(matches!(lines[1].result, Ok(RepairOk::Valid)));
+
+        let options = RepairOptions::new()
+            .allow(Rule::Bom)
+            .invalid_line(InvalidLine::Skip);
+        assert!(matches!(
+            repair_lines(options, b"\xFF\xFE1\x00\n\x002\x00").0,
+            Err(RepairErr::Invalid(SyntaxError::Utf16Unsupported { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_repair_stream() {
+        use super::{
+            CommentMode, Fix, FixKind, RepairErr, RepairOk, RepairOptions, Rule, Span, Stream,
+            SyntaxError,
+        };
+
+        let input = r#" {"a": 1,}{"b": 2} 3 4"x""#;
+        assert!(repair(input).0.is_err());
+
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Keep), input);
+        assert_eq!(r#" {"a": 1}{"b": 2} 3 4 "x""#, out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(8, 9),
+                    output: Span::empty(8),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::empty(22),
+                    output: Span::new(21, 22),
+                },
+            ]
+        );
+
+        // Repaired scalars are kept apart from the next value so that the output can be read back.
+        let options = RepairOptions::new()
+            .stream(Stream::Keep)
+            .allow(Rule::Exponent)
+            .allow(Rule::DecimalPoint);
+        for (input, expected) in [("true1e[.5]", "true 1e0 [0.5]"), (".5\"a\"", "0.5 \"a\"")] {
+            let (res, out) = repair_with(options.clone(), input);
+            assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+            assert_eq!(expected, out);
         }
-        {
-            let s = r#"{"a":1   "b":2  }"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!(r#"{"a":1,   "b":2  }"#, out);
+        // A malformed number is not split into values.
+        for input in [".5.5", "1.2.3", "1e.5", "01", "1x"] {
+            assert!(matches!(
+                repair_with(options.clone(), input).0,
+                Err(RepairErr::Invalid(SyntaxError::TrailingData { .. }))
+            ));
         }
-        {
-            let s = r#"{"a":1   "b":2  ,}"#;
-            let (res, out) = repair(s);
-            assert!(matches!(res, Ok(super::RepairOk::Repaired)));
-            assert_eq!(r#"{"a":1,   "b":2  }"#, out);
+
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Lines), input);
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!("{\"a\": 1}\n{\"b\": 2}\n3\n4\n\"x\"\n", out);
+
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Array), input);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(r#"[{"a": 1},{"b": 2},3,4,"x"]"#, out);
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::ArrayWrapped,
+                    input: Span::new(0, 1),
+                    output: Span::new(0, 1),
+                },
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(8, 9),
+                    output: Span::empty(8),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::empty(10),
+                    output: Span::new(9, 10),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::new(18, 19),
+                    output: Span::new(18, 19),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::new(20, 21),
+                    output: Span::new(20, 21),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::empty(22),
+                    output: Span::new(22, 23),
+                },
+                Fix {
+                    kind: FixKind::ArrayWrapped,
+                    input: Span::empty(25),
+                    output: Span::new(26, 27),
+                },
+            ]
+        );
+
+        let mut values = Vec::new();
+        super::repair_stream(&RepairOptions::new(), input.as_bytes(), |value| {
+            let output = String::from_utf8(value.output.to_vec()).unwrap();
+            values.push((value.input, output, value.result));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            values
+                .iter()
+                .map(|(input, output, _)| (*input, output.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (Span::new(1, 10), r#"{"a": 1}"#),
+                (Span::new(10, 18), r#"{"b": 2}"#),
+                (Span::new(19, 20), "3"),
+                (Span::new(21, 22), "4"),
+                (Span::new(22, 25), r#""x""#),
+            ]
+        );
+        let RepairOk::Repaired(report) = &values[0].2 else {
+            panic!("expected repaired");
+        };
+        assert_eq!(report.fixes()[0].output, Span::empty(7));
+        assert!(
+            values[1..]
+                .iter()
+                .all(|(_, _, result)| matches!(result, RepairOk::Valid))
+        );
+
+        for input in ["1\n2\n", "   "] {
+            let (res, out) = repair_with(RepairOptions::new().stream(Stream::Lines), input);
+            assert!(matches!(res, Ok(RepairOk::Valid)));
+            assert_eq!(input, out);
+        }
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Lines), "1 2");
+        assert_eq!("1\n2\n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::new(1, 2),
+                    output: Span::new(1, 2),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::empty(3),
+                    output: Span::new(3, 4),
+                },
+            ]
+        );
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Array), "1 2");
+        assert_eq!("[1,2]", out);
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        let (res, out) = repair_with(RepairOptions::new().stream(Stream::Array), " ");
+        assert_eq!("[]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::ArrayWrapped,
+                input: Span::new(0, 1),
+                output: Span::new(0, 2),
+            }]
+        );
+        assert!(
+            repair_with(RepairOptions::new().stream(Stream::Keep), "{} {")
+                .0
+                .is_err()
+        );
+
+        let options = |mode| RepairOptions::new().stream(mode).allow(Rule::Comment);
+        let (res, out) = repair_with(options(Stream::Array), "[1] // done\n");
+        assert_eq!("[[1]]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::ArrayWrapped,
+                    input: Span::empty(0),
+                    output: Span::new(0, 1),
+                },
+                Fix {
+                    kind: FixKind::ArrayWrapped,
+                    input: Span::new(3, 12),
+                    output: Span::new(4, 5),
+                },
+                Fix {
+                    kind: FixKind::CommentRemoved,
+                    input: Span::new(4, 11),
+                    output: Span::empty(4),
+                },
+            ]
+        );
+        let (_, out) = repair_with(options(Stream::Array), "{} /* x */");
+        assert_eq!("[{}]", out);
+        let (res, out) = repair_with(options(Stream::Lines), "1 /*x*/ 2\n");
+        assert_eq!("1\n2\n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::new(1, 8),
+                    output: Span::new(1, 2),
+                },
+                Fix {
+                    kind: FixKind::CommentRemoved,
+                    input: Span::new(2, 7),
+                    output: Span::empty(1),
+                },
+            ]
+        );
+        let (_, out) = repair_with(options(Stream::Keep), "1/*x*/2 /*y*/ 3//z");
+        assert_eq!("1 2  3", out);
+        let (res, out) = repair_with(
+            options(Stream::Keep).comment_mode(CommentMode::Whitespace),
+            "1 /*x*/ 2",
+        );
+        assert_eq!("1       2", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::CommentRemoved,
+                input: Span::new(2, 7),
+                output: Span::new(2, 7),
+            }]
+        );
+        assert!(repair_with(options(Stream::Array), "1 /* x").0.is_err());
+        assert!(repair_with(options(Stream::Array), "1 / 2").0.is_err());
+
+        let input = b"\xEF\xBB\xBF1 2";
+        let options = || RepairOptions::new().stream(Stream::Array);
+        assert!(repair_with(options(), input).0.is_err());
+        let (res, out) = repair_with(options().keep_bom(true), input);
+        assert_eq!("\u{FEFF}[1,2]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[..2],
+            [
+                Fix {
+                    kind: FixKind::ArrayWrapped,
+                    input: Span::empty(3),
+                    output: Span::new(3, 4),
+                },
+                Fix {
+                    kind: FixKind::SeparatorReplaced,
+                    input: Span::new(4, 5),
+                    output: Span::new(5, 6),
+                },
+            ]
+        );
+        let (res, out) = repair_with(options().allow(Rule::Bom), input);
+        assert_eq!("[1,2]", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[0],
+            Fix {
+                kind: FixKind::BomRemoved,
+                input: Span::new(0, 3),
+                output: Span::empty(0),
+            }
+        );
+        let (res, out) = repair_with(
+            RepairOptions::new().stream(Stream::Keep).allow(Rule::Bom),
+            b"\xFF\xFE1\x00 \x002\x00",
+        );
+        assert_eq!("1 2", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::Utf16Transcoded,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+                Fix {
+                    kind: FixKind::BomRemoved,
+                    input: Span::new(0, 3),
+                    output: Span::empty(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_sequence() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Rule, Span, Stream};
+
+        let options = || RepairOptions::new().stream(Stream::Sequence);
+        let input = "\x1e{\"a\": 1,}\n\x1e12\x1e{\"b\": \n\x1e\x1e3\n\x1exyz\n";
+        let (res, out) = repair_with(options(), input);
+        assert_eq!("\x1e{\"a\": 1}\n\x1e3\n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::TrailingCommaRemoved,
+                    input: Span::new(8, 9),
+                    output: Span::empty(8),
+                },
+                Fix {
+                    kind: FixKind::TruncatedElementRemoved,
+                    input: Span::new(11, 14),
+                    output: Span::empty(10),
+                },
+                Fix {
+                    kind: FixKind::TruncatedElementRemoved,
+                    input: Span::new(14, 22),
+                    output: Span::empty(10),
+                },
+                Fix {
+                    kind: FixKind::EmptyElementRemoved,
+                    input: Span::new(22, 23),
+                    output: Span::empty(10),
+                },
+                Fix {
+                    kind: FixKind::InvalidElementRemoved,
+                    input: Span::new(26, 31),
+                    output: Span::empty(13),
+                },
+            ]
+        );
+
+        let input = "\x1e{\"a\": 1}\n\x1e\"b\"\n";
+        let (res, out) = repair_with(options(), input);
+        assert!(matches!(res, Ok(RepairOk::Valid)));
+        assert_eq!(input, out);
+
+        for (input, removed) in [
+            ("\x1e\x1e1\n", Span::new(0, 1)),
+            ("  \x1e1\n", Span::new(0, 2)),
+        ] {
+            let (res, out) = repair_with(options(), input);
+            assert_eq!("\x1e1\n", out);
+            let Ok(RepairOk::Repaired(report)) = res else {
+                panic!("expected repaired");
+            };
+            assert_eq!(
+                report.fixes(),
+                [Fix {
+                    kind: FixKind::EmptyElementRemoved,
+                    input: removed,
+                    output: Span::empty(0),
+                }]
+            );
+        }
+
+        let (res, out) = repair_with(options(), "[1]\x1e2 \x1e");
+        assert_eq!("\x1e[1]\n\x1e2 \n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::RecordSeparatorInserted,
+                    input: Span::empty(0),
+                    output: Span::new(0, 1),
+                },
+                Fix {
+                    kind: FixKind::LineFeedInserted,
+                    input: Span::empty(3),
+                    output: Span::new(4, 5),
+                },
+                Fix {
+                    kind: FixKind::LineFeedInserted,
+                    input: Span::empty(6),
+                    output: Span::new(8, 9),
+                },
+                Fix {
+                    kind: FixKind::EmptyElementRemoved,
+                    input: Span::new(6, 7),
+                    output: Span::empty(9),
+                },
+            ]
+        );
+
+        let (res, out) = repair_with(options(), "\x1e{\"a\":1}");
+        assert_eq!("\x1e{\"a\":1}\n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [Fix {
+                kind: FixKind::LineFeedInserted,
+                input: Span::empty(8),
+                output: Span::new(8, 9),
+            }]
+        );
+
+        let mut values = Vec::new();
+        super::repair_stream(&options(), "\x1e1\n\x1e[".as_bytes(), |value| {
+            values.push((value.input, value.output.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            values,
+            [
+                (Span::new(1, 3), b"1\n".to_vec()),
+                (Span::new(4, 5), Vec::new()),
+            ]
+        );
+
+        let input = b"\xEF\xBB\xBF\x1e1\n";
+        let (res, out) = repair_with(options().keep_bom(true), input);
+        assert!(matches!(res, Ok(RepairOk::Valid)));
+        assert_eq!(input, out.as_bytes());
+        let (res, out) = repair_with(options().keep_bom(true), b"\xEF\xBB\xBF[1]");
+        assert_eq!("\u{FEFF}\x1e[1]\n", out);
+        let Ok(RepairOk::Repaired(report)) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes()[0],
+            Fix {
+                kind: FixKind::RecordSeparatorInserted,
+                input: Span::empty(3),
+                output: Span::new(3, 4),
+            }
+        );
+        assert!(repair_with(options(), input).0.is_err());
+        let (_, out) = repair_with(options().allow(Rule::Bom), input);
+        assert_eq!("\x1e1\n", out);
+        let (_, out) = repair_with(options().allow(Rule::Bom), b"\xFE\xFF\x00\x1e\x001\x00\n");
+        assert_eq!("\x1e1\n", out);
+    }
+
+    #[test]
+    fn test_repairer() {
+        use super::{Fix, FixKind, RepairOk, RepairOptions, Repairer, Rule, Span, Stream};
+
+        let mut repairer = Repairer::new(&RepairOptions::new().allow(Rule::Truncation));
+        let mut chunks = Vec::new();
+        for chunk in [r#"{"a": [1, 2"#, r#", tr"#, r#"ue], "b": "hel"#] {
+            chunks.push(String::from_utf8(repairer.feed(chunk.as_bytes()).unwrap()).unwrap());
+        }
+        assert_eq!(chunks, [r#"{"a": [1"#, ", 2", r#", true], "b": "hel"#]);
+        let (rest, res) = repairer.finish().unwrap();
+        assert_eq!(r#""}"#, String::from_utf8(rest).unwrap());
+        let RepairOk::Repaired(report) = res else {
+            panic!("expected repaired");
+        };
+        assert_eq!(
+            report.fixes(),
+            [
+                Fix {
+                    kind: FixKind::UnterminatedStringClosed,
+                    input: Span::empty(29),
+                    output: Span::new(29, 30),
+                },
+                Fix {
+                    kind: FixKind::UnclosedContainerClosed,
+                    input: Span::empty(29),
+                    output: Span::new(30, 31),
+                },
+            ]
+        );
+
+        let mut repairer = Repairer::new(&RepairOptions::new());
+        assert!(repairer.feed(b"[1, 2").is_ok());
+        assert!(repairer.feed(b"}").is_err());
+
+        let mut repairer = Repairer::new(&RepairOptions::new().stream(Stream::Lines));
+        let mut output = Vec::new();
+        for c in br#"{"a": 1,}[2] 3"# {
+            output.extend(repairer.feed(&[*c]).unwrap());
         }
+        let (rest, res) = repairer.finish().unwrap();
+        output.extend(rest);
+        assert!(matches!(res, RepairOk::Repaired(_)));
+        assert_eq!("{\"a\": 1}\n[2]\n3\n", String::from_utf8(output).unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_repair_async() {
+        use super::{RepairErr, RepairOk, RepairOptions, Rule, SyntaxError};
+
+        let mut output = Vec::new();
+        let res = super::repair_async(&b"[1, 2,]"[..], &mut output).await;
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!(b"[1, 2]", &output[..]);
+
+        let mut output = Vec::new();
+        let res = super::repair_async(&b"[1, 2"[..], &mut output).await;
+        assert!(res.is_err());
+
+        // The output written at the end of input is kept when an error is detected after it.
+        let options = RepairOptions::new().allow(Rule::Wrapper);
+        let mut output = Vec::new();
+        let res = super::repair_with_async(&options, &b"cb(0"[..], &mut output).await;
+        assert!(matches!(
+            res,
+            Err(RepairErr::Invalid(SyntaxError::UnexpectedEof { .. }))
+        ));
+        assert_eq!(b"0", &output[..]);
+
+        let options = RepairOptions::new().allow(Rule::Truncation);
+        let mut output = Vec::new();
+        let res = super::repair_with_async(&options, &b"[1, 2"[..], &mut output).await;
+        assert!(matches!(res, Ok(RepairOk::Repaired(_))));
+        assert_eq!(b"[1, 2]", &output[..]);
+
+        let mut output = Vec::new();
+        let res = super::repair_async(&b"null"[..], &mut output).await;
+        assert!(matches!(res, Ok(RepairOk::Valid)));
+        assert_eq!(b"null", &output[..]);
     }
 }
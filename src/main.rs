@@ -1,60 +1,292 @@
-use reparojson::{self, RepairErr, RepairOk, RepairResult};
+use reparojson::{
+    self, CommentMode, Extract, InvalidLine, LiteralSpelling, LoneKey, NonFinite, RawValue,
+    RepairErr, RepairOk, RepairOptions, RepairResult, Rule, Stream, Utf8Replacement,
+};
 use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::{stdin, stdout, BufReader, BufWriter, Write};
+use std::io::{stdin, stdout, BufReader, BufWriter, Read, Write};
 use std::process::ExitCode;
 
 struct Config {
     quiet: bool,
+    lines: bool,
     file_path: Option<OsString>,
+    options: RepairOptions,
 }
 
 fn parse_args() -> std::io::Result<Config> {
-    use clap::{arg, command, value_parser};
+    use clap::builder::PossibleValuesParser;
+    use clap::error::ErrorKind;
+    use clap::{arg, command, value_parser, ArgAction};
 
-    let matches = command!()
+    let rule_names = || PossibleValuesParser::new(Rule::ALL.iter().map(|rule| rule.name()));
+
+    let mut command = command!()
         .arg(arg!(-q --quiet "Successfully exit if the input JSON is repaired"))
+        .arg(
+            arg!(--allow <RULE> "Enable the repair RULE")
+                .action(ArgAction::Append)
+                .value_parser(rule_names()),
+        )
+        .arg(
+            arg!(--deny <RULE> "Disable the repair RULE (overrides --allow)")
+                .action(ArgAction::Append)
+                .value_parser(rule_names()),
+        )
+        .arg(
+            arg!(--"comment-mode" <MODE> "How to remove comments for the comment rule")
+                .value_parser(["strip", "whitespace"])
+                .default_value("strip"),
+        )
+        .arg(
+            arg!(--literal <MAPPING> "Map a literal for the literal rule, given as SPELLING=JSON")
+                .action(ArgAction::Append)
+                .value_parser(parse_literal_mapping),
+        )
+        .arg(arg!(
+            --"no-default-literals" "Do not replace True, False, None and undefined for the literal rule"
+        ))
+        .arg(
+            arg!(--"non-finite" <TARGET> "What NaN and Infinity become for the literal rule")
+                .value_parser(["null", "string", "error"])
+                .default_value("null"),
+        )
+        .arg(
+            arg!(--"utf8-replacement" <TARGET> "What invalid UTF-8 becomes for the invalid-utf8 rule")
+                .value_parser(["char", "escape"])
+                .default_value("char"),
+        )
+        .arg(
+            arg!(--placeholder <JSON> "What the missing-value and lone-key rules insert")
+                .value_parser(parse_raw_value)
+                .default_value("null"),
+        )
+        .arg(
+            arg!(--"lone-key" <MODE> "How to repair a key without a value for the lone-key rule")
+                .value_parser(["fill", "drop"])
+                .default_value("fill"),
+        )
+        .arg(arg!(--"check-surrogates" "Reject unpaired surrogate escapes unless repaired"))
+        .arg(arg!(--"keep-bom" "Keep the byte order mark instead of rejecting it"))
+        .arg(
+            arg!(--extract <MODE> "Extract the first or all JSON values from surrounding text")
+                .value_parser(["first", "all"]),
+        )
+        .arg(
+            arg!(--stream <MODE> "Repair a sequence of JSON values, keeping whitespace between them, separating them by line breaks or an array, or as an RFC 7464 JSON text sequence")
+                .value_parser(["keep", "lines", "array", "sequence"])
+                .conflicts_with("extract"),
+        )
+        .arg(
+            arg!(--lines "Repair each line of the input as a separate JSON value (NDJSON)")
+                .conflicts_with_all(["extract", "stream"]),
+        )
+        .arg(
+            arg!(--"invalid-line" <ACTION> "What to do with a line that cannot be repaired in --lines mode")
+                .value_parser(["fail", "skip", "pass"])
+                .default_value("pass")
+                .requires("lines"),
+        )
+        .arg(
+            arg!(--"max-depth" <N> "Reject the input nested deeper than N levels")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"max-size" <BYTES> "Reject the input larger than BYTES")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"max-string-length" <BYTES> "Reject strings longer than BYTES")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"max-number-length" <BYTES> "Reject numbers longer than BYTES")
+                .value_parser(value_parser!(u64)),
+        )
         .arg(
             arg!([FILE] "The input JSON file (default: STDIN)")
                 .value_parser(value_parser!(OsString)),
-        )
-        .get_matches();
+        );
+    let matches = command.get_matches_mut();
 
     let quiet = matches.get_flag("quiet");
+    let lines = matches.get_flag("lines");
     let file_path = matches.get_one("FILE").cloned();
-    Ok(Config { quiet, file_path })
+
+    let mut options = RepairOptions::new();
+    for name in matches.get_many::<String>("allow").unwrap_or_default() {
+        options = options.allow(Rule::from_name(name).expect("validated by clap"));
+    }
+    for name in matches.get_many::<String>("deny").unwrap_or_default() {
+        options = options.deny(Rule::from_name(name).expect("validated by clap"));
+    }
+    if matches.contains_id("stream") && options.is_allowed(Rule::Wrapper) {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the wrapper rule cannot be used with '--stream <MODE>'",
+            )
+            .exit();
+    }
+    match matches.get_one::<String>("comment-mode").map(String::as_str) {
+        Some("whitespace") => options = options.comment_mode(CommentMode::Whitespace),
+        _ => options = options.comment_mode(CommentMode::Strip),
+    }
+    for (spelling, replacement) in matches
+        .get_many::<(LiteralSpelling, RawValue)>("literal")
+        .unwrap_or_default()
+    {
+        options = options.literal(spelling.clone(), replacement.clone());
+    }
+    options = options.default_literals(!matches.get_flag("no-default-literals"));
+    match matches.get_one::<String>("non-finite").map(String::as_str) {
+        Some("string") => options = options.non_finite(NonFinite::String),
+        Some("error") => options = options.non_finite(NonFinite::Error),
+        _ => options = options.non_finite(NonFinite::Null),
+    }
+    match matches.get_one::<String>("utf8-replacement").map(String::as_str) {
+        Some("escape") => options = options.utf8_replacement(Utf8Replacement::Escape),
+        _ => options = options.utf8_replacement(Utf8Replacement::Char),
+    }
+    if let Some(placeholder) = matches.get_one::<RawValue>("placeholder") {
+        options = options.placeholder(placeholder.clone());
+    }
+    match matches.get_one::<String>("lone-key").map(String::as_str) {
+        Some("drop") => options = options.lone_key(LoneKey::Drop),
+        _ => options = options.lone_key(LoneKey::Fill),
+    }
+    options = options.check_surrogates(matches.get_flag("check-surrogates"));
+    options = options.keep_bom(matches.get_flag("keep-bom"));
+    match matches.get_one::<String>("extract").map(String::as_str) {
+        Some("first") => options = options.extract(Extract::First),
+        Some("all") => options = options.extract(Extract::All),
+        _ => {}
+    }
+    match matches.get_one::<String>("stream").map(String::as_str) {
+        Some("keep") => options = options.stream(Stream::Keep),
+        Some("lines") => options = options.stream(Stream::Lines),
+        Some("array") => options = options.stream(Stream::Array),
+        Some("sequence") => options = options.stream(Stream::Sequence),
+        _ => {}
+    }
+    match matches.get_one::<String>("invalid-line").map(String::as_str) {
+        Some("fail") => options = options.invalid_line(InvalidLine::Fail),
+        Some("skip") => options = options.invalid_line(InvalidLine::Skip),
+        _ => options = options.invalid_line(InvalidLine::PassThrough),
+    }
+    if let Some(&limit) = matches.get_one("max-depth") {
+        options = options.max_depth(limit);
+    }
+    if let Some(&limit) = matches.get_one("max-size") {
+        options = options.max_size(limit);
+    }
+    if let Some(&limit) = matches.get_one("max-string-length") {
+        options = options.max_string_length(limit);
+    }
+    if let Some(&limit) = matches.get_one("max-number-length") {
+        options = options.max_number_length(limit);
+    }
+
+    Ok(Config {
+        quiet,
+        lines,
+        file_path,
+        options,
+    })
 }
 
-fn repair(input_file_path: Option<OsString>, mut w: impl Write) -> RepairResult {
-    match input_file_path.as_ref() {
-        None => {
+fn parse_literal_mapping(s: &str) -> Result<(LiteralSpelling, RawValue), String> {
+    let Some((spelling, replacement)) = s.split_once('=') else {
+        return Err("expected SPELLING=JSON".to_owned());
+    };
+    let replacement = parse_raw_value(replacement)?;
+    let Some(spelling) = LiteralSpelling::new(spelling) else {
+        return Err(format!("'{}' cannot be matched as a literal", spelling));
+    };
+    Ok((spelling, replacement))
+}
+
+fn parse_raw_value(s: &str) -> Result<RawValue, String> {
+    s.parse::<RawValue>()
+        .map_err(|err| format!("{}: {}", err.position(), err))
+}
+
+fn open(input_file_path: Option<&OsStr>) -> std::io::Result<Box<dyn Read>> {
+    match input_file_path {
+        Some(file_path) if file_path != OsStr::new("-") => {
+            let reader = File::open(file_path)?;
+            Ok(Box::new(BufReader::new(reader)))
+        }
+        _ => {
             let reader = stdin().lock();
-            let reader = BufReader::new(reader);
-            reparojson::repair(reader, &mut w)
+            Ok(Box::new(BufReader::new(reader)))
         }
-        Some(file_path) => {
-            if file_path == OsStr::new("-") {
-                let reader = stdin().lock();
-                let reader = BufReader::new(reader);
-                reparojson::repair(reader, &mut w)
-            } else {
-                let reader = File::open(file_path)?;
-                let reader = BufReader::new(reader);
-                reparojson::repair(reader, &mut w)
+    }
+}
+
+fn repair(
+    input_file_path: Option<&OsStr>,
+    options: &RepairOptions,
+    mut w: impl Write,
+) -> RepairResult {
+    let reader = open(input_file_path)?;
+    reparojson::repair_with(options, reader, &mut w)
+}
+
+/// Repairs each line of the input and reports the lines that cannot be repaired. Returns the
+/// result of the lines that were repaired and whether any line could not be repaired.
+fn repair_lines(
+    input_file_path: Option<&OsStr>,
+    options: &RepairOptions,
+    input_name: &str,
+    mut w: impl Write,
+) -> Result<(RepairOk, bool), RepairErr> {
+    let reader = open(input_file_path)?;
+    let mut repaired = false;
+    let mut invalid = false;
+    reparojson::repair_lines(options, reader, &mut w, |line| {
+        match line.result {
+            Ok(RepairOk::Valid) => {}
+            Ok(RepairOk::Repaired(_)) => repaired = true,
+            Err(err) => {
+                eprintln!("{}:{}: {}", input_name, err.position(), err);
+                invalid = true;
             }
         }
+        Ok(())
+    })?;
+    if repaired {
+        Ok((RepairOk::Repaired(Default::default()), invalid))
+    } else {
+        Ok((RepairOk::Valid, invalid))
     }
 }
 
 fn main() -> std::io::Result<ExitCode> {
     let config = parse_args()?;
 
+    let input_name = match config.file_path.as_ref() {
+        Some(file_path) if file_path != OsStr::new("-") => file_path.to_string_lossy().into_owned(),
+        _ => "<stdin>".to_owned(),
+    };
+
     let writer = stdout().lock();
     let mut writer = BufWriter::new(writer);
 
-    let exit_code = match repair(config.file_path, &mut writer) {
+    let file_path = config.file_path.as_deref();
+    let (result, invalid_lines) = if config.lines {
+        match repair_lines(file_path, &config.options, &input_name, &mut writer) {
+            Ok((ok, invalid)) => (Ok(ok), invalid),
+            Err(err) => (Err(err), false),
+        }
+    } else {
+        (repair(file_path, &config.options, &mut writer), false)
+    };
+    let exit_code = match result {
+        // The lines that cannot be repaired have been reported.
+        Ok(_) if invalid_lines => ExitCode::from(2),
         Ok(RepairOk::Valid) => ExitCode::SUCCESS,
-        Ok(RepairOk::Repaired) => {
+        Ok(RepairOk::Repaired(_)) => {
             if config.quiet {
                 ExitCode::SUCCESS
             } else {
@@ -62,7 +294,7 @@ fn main() -> std::io::Result<ExitCode> {
             }
         }
         Err(RepairErr::Invalid(err)) => {
-            eprintln!("{}", err);
+            eprintln!("{}:{}: {}", input_name, err.position(), err);
             ExitCode::from(2)
         }
         Err(RepairErr::IoErr(err)) => {